@@ -0,0 +1,199 @@
+//! Bundler metafile-related code
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::configurations::Cli;
+
+/// Extensions of the bundle outputs that are loaded by Node.js
+static JS_EXTENSIONS: &[&str] = &["js", "cjs", "mjs"];
+
+/// A bundle output and the external modules it loads from `node_modules`
+#[derive(Debug, PartialEq)]
+pub struct BundleOutput {
+    pub path: PathBuf,
+    /// External module specifiers, each with whether it is loaded through `require`
+    pub externals: Vec<(String, bool)>,
+}
+
+fn is_js_output(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| JS_EXTENSIONS.contains(&extension))
+}
+
+/// Parses an esbuild metafile, whose output paths are relative to the working directory
+fn parse_esbuild_metafile(base_location: &Path, metafile: &Value) -> Vec<BundleOutput> {
+    metafile["outputs"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(output, metadata)| BundleOutput {
+            path: base_location.join(output),
+            externals: metadata["imports"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|import| import["external"].as_bool() == Some(true))
+                .filter_map(|import| {
+                    let is_cjs = matches!(
+                        import["kind"].as_str(),
+                        Some("require-call" | "require-resolve")
+                    );
+                    import["path"]
+                        .as_str()
+                        .map(|specifier| (specifier.to_owned(), is_cjs))
+                })
+                .collect(),
+        })
+        .filter(|output| is_js_output(&output.path))
+        .collect()
+}
+
+/// Parses a webpack external module identifier, like `external commonjs "fastify"`
+fn parse_webpack_external(identifier: &str) -> Option<(String, bool)> {
+    let external = identifier.strip_prefix("external ")?;
+    let (external_type, request) = external.split_once('"')?;
+    let request = request.strip_suffix('"')?;
+    let is_cjs = !matches!(external_type.trim(), "module" | "import");
+
+    Some((request.to_owned(), is_cjs))
+}
+
+/// Parses a webpack stats JSON, including the stats of every child compilation
+fn parse_webpack_stats(base_location: &Path, stats: &Value) -> Vec<BundleOutput> {
+    let output_location = base_location.join(stats["outputPath"].as_str().unwrap_or_default());
+
+    let externals: Vec<(String, bool)> = stats["modules"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|module| module["identifier"].as_str())
+        .filter_map(parse_webpack_external)
+        .collect();
+
+    let children = stats["children"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|child| parse_webpack_stats(base_location, child));
+
+    stats["assets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|asset| asset["name"].as_str())
+        .map(|asset| BundleOutput {
+            path: output_location.join(asset),
+            externals: externals.clone(),
+        })
+        .filter(|output| is_js_output(&output.path))
+        .chain(children)
+        .collect()
+}
+
+/// Retrieves the outputs of the bundle described by the metafile
+///
+/// Both esbuild metafiles and webpack stats JSON are supported.
+pub fn retrieve_bundle_outputs(configurations: &Cli) -> Vec<BundleOutput> {
+    let Some(metafile_location) = &configurations.metafile_location else {
+        return Vec::new();
+    };
+    let metafile_location = configurations.project_root_location.join(metafile_location);
+
+    let metafile = match fs::read_to_string(&metafile_location) {
+        Ok(metafile) => metafile,
+        Err(error) => {
+            println!(
+                "Failed to read file {}: {}",
+                metafile_location.display(),
+                error
+            );
+            return Vec::new();
+        }
+    };
+
+    match serde_json::from_str::<Value>(&metafile) {
+        Ok(metafile) if metafile["outputs"].is_object() => {
+            parse_esbuild_metafile(&configurations.project_root_location, &metafile)
+        }
+        Ok(metafile) => parse_webpack_stats(&configurations.project_root_location, &metafile),
+        Err(error) => {
+            println!(
+                "Failed to parse file {}: {}",
+                metafile_location.display(),
+                error
+            );
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_esbuild_metafile() {
+        let metafile = serde_json::json!({
+            "inputs": {},
+            "outputs": {
+                "dist/index.js": {
+                    "imports": [
+                        { "path": "fastify", "kind": "require-call", "external": true },
+                        { "path": "pino", "kind": "import-statement", "external": true },
+                        { "path": "src/chunk.js", "kind": "import-statement" }
+                    ],
+                    "entryPoint": "src/index.ts"
+                },
+                "dist/index.js.map": { "imports": [] }
+            }
+        });
+
+        assert_eq!(
+            parse_esbuild_metafile(Path::new("/app"), &metafile),
+            vec![BundleOutput {
+                path: PathBuf::from("/app/dist/index.js"),
+                externals: vec![("fastify".to_owned(), true), ("pino".to_owned(), false)],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_webpack_stats() {
+        let stats = serde_json::json!({
+            "outputPath": "/app/dist",
+            "assets": [{ "name": "main.js" }, { "name": "main.js.map" }],
+            "modules": [
+                { "identifier": "/app/src/index.js" },
+                { "identifier": "external commonjs \"fastify\"" },
+                { "identifier": "external module \"pino\"" }
+            ],
+            "children": [
+                {
+                    "outputPath": "worker",
+                    "assets": [{ "name": "worker.cjs" }],
+                    "modules": [{ "identifier": "external \"busboy\"" }]
+                }
+            ]
+        });
+
+        assert_eq!(
+            parse_webpack_stats(Path::new("/app"), &stats),
+            vec![
+                BundleOutput {
+                    path: PathBuf::from("/app/dist/main.js"),
+                    externals: vec![("fastify".to_owned(), true), ("pino".to_owned(), false)],
+                },
+                BundleOutput {
+                    path: PathBuf::from("/app/worker/worker.cjs"),
+                    externals: vec![("busboy".to_owned(), true)],
+                },
+            ]
+        );
+    }
+}
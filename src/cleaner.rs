@@ -1,71 +1,158 @@
 //! Cleaner-related code
 
-use std::{collections::HashSet, fs, path::PathBuf};
-
-use crate::{configurations::Cli, glob::retrieve_glob_paths};
-use remove_empty_subdirs::remove_empty_subdirs;
-
-/// List of glob patterns for garbage items to remove
-static STATIC_GARBAGE_ITEMS: &[&str] = &[
-    // folders
-    "@types",
-    "bench",
-    "browser",
-    "docs",
-    "example",
-    "examples",
-    "test",
-    "tests",
-    "benchmark",
-    "integration",
-    ".bin",
-    // extensions
-    "*.md",
-    "*.markdown",
-    "*.map",
-    "*.ts",
-    // specific files
-    "license",
-    "contributing",
-    ".nycrc",
-    "makefile",
-    ".DS_Store",
-    ".markdownlint-cli2.yaml",
-    ".editorconfig",
-    ".nvmrc",
-    "bower.json",
-    ".airtap.yml",
-    "jenkinsfile",
-    "makefile",
-    ".snyk",
-    // generic files
-    ".*ignore",
-    "*eslint*",
-    "*stylelint*",
-    "*.min.*",
-    "browser.*js",
-    ".travis.*",
-    ".coveralls.*",
-    "tsconfig.*",
-    ".prettierrc*",
-    "*.bak",
-    "karma.conf.*",
-    ".git*",
-    ".tap*",
-    ".c8*",
-    "gulpfile.*",
-    "gruntfile.*",
-    ".npm*",
-    "yarn*",
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob::Pattern;
+
+use crate::{
+    configurations::{Cli, StaticCategory},
+    exclusion::Exclusion,
+    glob::retrieve_glob_paths,
+    module_graph::Visitor,
+    package::{package_root, retrieve_referenced_paths},
+    presets::{self, Preset},
+    project_config::ProjectConfig,
+};
+
+/// Glob patterns for garbage items to remove, grouped by category
+static STATIC_GARBAGE_CATEGORIES: &[(StaticCategory, &[&str])] = &[
+    (
+        StaticCategory::Docs,
+        &[
+            "docs",
+            "example",
+            "examples",
+            "*.md",
+            "*.markdown",
+            "license",
+            "contributing",
+        ],
+    ),
+    (
+        StaticCategory::Tests,
+        &[
+            "bench",
+            "benchmark",
+            "test",
+            "tests",
+            "integration",
+            ".nycrc",
+            ".airtap.yml",
+            "karma.conf.*",
+            ".tap*",
+            ".c8*",
+        ],
+    ),
+    (StaticCategory::Typings, &["@types", "*.ts"]),
+    (StaticCategory::Sourcemaps, &["*.map"]),
+    (
+        StaticCategory::Lint,
+        &[
+            ".DS_Store",
+            ".markdownlint-cli2.yaml",
+            ".editorconfig",
+            "*eslint*",
+            "*stylelint*",
+            ".prettierrc*",
+        ],
+    ),
+    (
+        StaticCategory::Ci,
+        &["jenkinsfile", ".snyk", ".travis.*", ".coveralls.*", ".git*"],
+    ),
+    (
+        StaticCategory::Build,
+        &[
+            "browser",
+            "browser.*js",
+            "makefile",
+            "tsconfig.*",
+            "*.bak",
+            "gulpfile.*",
+            "gruntfile.*",
+        ],
+    ),
+    (
+        StaticCategory::PackageManager,
+        &[".bin", ".nvmrc", "bower.json", ".*ignore", ".npm*", "yarn*"],
+    ),
+    (StaticCategory::Minified, &["*.min.*"]),
+];
+
+const NODE_MODULES: &str = "node_modules";
+
+/// Package-manager caches, relative to the home directory
+static NPM_CACHE_ITEMS: &[&str] = &[".npm"];
+static PNPM_CACHE_ITEMS: &[&str] = &[".local/share/pnpm", ".cache/pnpm", ".pnpm-state"];
+static YARN_CACHE_ITEMS: &[&str] = &[".cache/yarn", ".yarn/berry/cache"];
+static BUN_CACHE_ITEMS: &[&str] = &[".bun/install/cache"];
+
+/// Package-manager caches and lock files, relative to the project root
+static YARN_PROJECT_CACHE_ITEMS: &[&str] = &[".yarn/cache"];
+static LOCK_FILES: &[&str] = &[
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lock",
+    "bun.lockb",
 ];
 
+/// Returns every static garbage item, and the ones of the selected categories
+fn retrieve_static_garbage_items(configurations: &Cli) -> (Vec<&'static str>, Vec<&'static str>) {
+    let all_items = STATIC_GARBAGE_CATEGORIES
+        .iter()
+        .flat_map(|(_, items)| items.iter().copied())
+        .collect();
+
+    let selected_items = STATIC_GARBAGE_CATEGORIES
+        .iter()
+        .filter(|(category, _)| {
+            configurations.static_categories.is_empty()
+                || configurations.static_categories.contains(category)
+        })
+        .filter(|(category, _)| !configurations.skip_categories.contains(category))
+        .flat_map(|(_, items)| items.iter().copied())
+        .collect();
+
+    (all_items, selected_items)
+}
+
 pub struct Cleaner<'a> {
     garbage: Vec<PathBuf>,
     configurations: &'a Cli,
+    exclusion: Exclusion,
 }
 
 impl<'a> Cleaner<'a> {
-    pub fn from_module_graph(configurations: &'a Cli, module_graph: &HashSet<PathBuf>) -> Self {
+    /// Creates a cleaner that removes the garbage, except for the kept paths
+    fn new(
+        configurations: &'a Cli,
+        applied_presets: &[(PathBuf, Preset)],
+        garbage: Vec<PathBuf>,
+    ) -> Self {
+        let exclusion = Exclusion::from_configurations(configurations, applied_presets);
+
+        Cleaner {
+            garbage: exclusion.apply(garbage),
+            configurations,
+            exclusion,
+        }
+    }
+
+    /// Removes the files that are not in the module graph
+    ///
+    /// Packages that the module graph never reaches are removed whole, together with their
+    /// manifest and nested `node_modules`. Manifests of the reached packages are kept.
+    pub fn from_module_graph(
+        configurations: &'a Cli,
+        applied_presets: &[(PathBuf, Preset)],
+        module_graph: &HashSet<PathBuf>,
+    ) -> Self {
         let node_modules_glob = configurations
             .project_root_location
             .join("**")
@@ -74,26 +161,70 @@ impl<'a> Cleaner<'a> {
 
         let package_json_filter = Some("package.json".as_ref());
 
-        let garbage = retrieve_glob_paths(vec![
+        let paths = retrieve_glob_paths(vec![
             node_modules_glob.join("*").display().to_string(),
             node_modules_glob.join(".*").display().to_string(),
-        ])
-        .into_iter()
-        .filter(|path| path.is_file())
-        .filter(|path| !module_graph.contains(path))
-        .filter(|path| path.file_name() != package_json_filter)
-        .collect();
+        ]);
 
-        Cleaner {
-            garbage,
-            configurations,
-        }
+        let reached_packages: HashSet<PathBuf> = module_graph
+            .iter()
+            .filter_map(|path| package_root(path))
+            .collect();
+        let reached_dirs: HashSet<&Path> = module_graph
+            .iter()
+            .flat_map(|path| path.ancestors())
+            .collect();
+
+        let unreached_packages: HashSet<PathBuf> = paths
+            .iter()
+            .filter_map(|path| package_root(path))
+            .filter(|root| !reached_dirs.contains(root.as_path()))
+            .collect();
+        let is_in_unreached_package = |path: &Path| {
+            path.ancestors()
+                .skip(1)
+                .any(|dir| unreached_packages.contains(dir))
+        };
+
+        let mut garbage: Vec<PathBuf> = unreached_packages
+            .iter()
+            .filter(|root| !is_in_unreached_package(root))
+            .cloned()
+            .collect();
+        garbage.sort();
+
+        garbage.extend(
+            paths
+                .into_iter()
+                .filter(|path| path.is_file())
+                .filter(|path| !module_graph.contains(path))
+                .filter(|path| !unreached_packages.contains(path) && !is_in_unreached_package(path))
+                .filter(|path| {
+                    path.file_name() != package_json_filter
+                        || package_root(path).is_none_or(|root| !reached_packages.contains(&root))
+                }),
+        );
+
+        Self::new(configurations, applied_presets, garbage)
     }
 
-    pub fn from_static_garbage(configurations: &'a Cli) -> Self {
+    /// Removes the files and directories that match the static garbage patterns, as extended by
+    /// the project configuration file
+    ///
+    /// Matches that hold a file referenced by a package manifest are kept and reported: a package
+    /// whose entry sits in a matched directory (like a package named `test`) is kept whole, and
+    /// a matched entry (like a `main` pointing to a `.min.js` file) is kept with the files it
+    /// imports. Type declarations are not considered referenced.
+    pub fn from_static_garbage(
+        configurations: &'a Cli,
+        applied_presets: &[(PathBuf, Preset)],
+    ) -> Self {
+        let project_config = ProjectConfig::from_configurations(configurations);
         let mut garbage_glob = Vec::new();
 
-        for garbage_item in STATIC_GARBAGE_ITEMS {
+        let (all_items, selected_items) = retrieve_static_garbage_items(configurations);
+
+        for garbage_item in project_config.garbage_items(&all_items, &selected_items) {
             let garbage_path = configurations
                 .node_modules_location
                 .join("**")
@@ -102,9 +233,85 @@ impl<'a> Cleaner<'a> {
             garbage_glob.push(garbage_path.display().to_string());
         }
 
-        Cleaner {
-            garbage: retrieve_glob_paths(garbage_glob),
-            configurations,
+        for garbage_path in project_config.package_globs(&configurations.node_modules_location) {
+            garbage_glob.push(garbage_path.display().to_string());
+        }
+
+        garbage_glob.extend(presets::garbage_patterns(applied_presets));
+
+        let referenced_paths = retrieve_referenced_paths(&configurations.node_modules_location);
+        let garbage = retrieve_glob_paths(garbage_glob);
+        let is_in_garbage = |path: &Path| garbage.iter().any(|item| path.starts_with(item));
+
+        let referenced_entries: Vec<PathBuf> = referenced_paths
+            .into_iter()
+            .filter(|path| is_in_garbage(path))
+            .collect();
+
+        // A matched directory that contains an entry (like a package named `test`) keeps the
+        // whole package, since its entry may load any file of it
+        let referenced_packages: HashSet<PathBuf> = referenced_entries
+            .iter()
+            .filter(|path| !garbage.contains(path))
+            .filter_map(|path| package_root(path))
+            .collect();
+
+        // The files the entries import (like a `.min.js` entry requiring another `.min.js`)
+        let reached_paths = Visitor::from_files(referenced_entries, true).run();
+
+        let mut exempted: Vec<&PathBuf> = referenced_packages
+            .iter()
+            .chain(&reached_paths)
+            .filter(|path| is_in_garbage(path))
+            .filter(|path| {
+                path.ancestors()
+                    .skip(1)
+                    .all(|dir| !referenced_packages.contains(dir))
+            })
+            .collect();
+        exempted.sort();
+
+        let exempted_patterns: Vec<String> = exempted
+            .iter()
+            .map(|path| Pattern::escape(&path.display().to_string()))
+            .collect();
+        let garbage = Exclusion::new(&configurations.project_root_location, &exempted_patterns)
+            .apply(garbage);
+
+        if !exempted.is_empty() {
+            println!("Kept because referenced by a package manifest:");
+            exempted
+                .iter()
+                .for_each(|path| println!("{}", path.display()));
+        }
+
+        Self::new(configurations, applied_presets, garbage)
+    }
+
+    /// Adds more paths to remove, skipping the ones already covered by the garbage
+    pub fn extend_garbage(&mut self, garbage: Vec<PathBuf>) {
+        let garbage = self.exclusion.apply(garbage);
+        self.push_garbage(garbage);
+    }
+
+    /// Adds the binaries built for other platforms, which only the `--keep` patterns can exclude
+    ///
+    /// The presets keep the binaries of the packages that load them dynamically, whatever their
+    /// platform, so they do not apply here.
+    pub fn extend_platform_garbage(&mut self, garbage: Vec<PathBuf>) {
+        let exclusion = Exclusion::new(
+            &self.configurations.project_root_location,
+            &self.configurations.keep.clone().unwrap_or_default(),
+        );
+
+        self.push_garbage(exclusion.apply(garbage));
+    }
+
+    fn push_garbage(&mut self, garbage: Vec<PathBuf>) {
+        for path in garbage {
+            if !self.garbage.iter().any(|item| path.starts_with(item)) {
+                self.garbage.push(path);
+            }
         }
     }
 
@@ -135,57 +342,106 @@ impl<'a> Cleaner<'a> {
         }
     }
 
-    /// Removes empty directories
-    fn remove_empty_dirs(&self) {
-        match remove_empty_subdirs(&self.configurations.project_root_location) {
-            Ok(()) => println!("Removed empty directories"),
-            Err(err) => println!("Failed to remove empty directories: {err}"),
+    /// Returns the outermost `node_modules` directories under the directory
+    fn retrieve_node_modules_dirs(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+            .flat_map(|entry| {
+                if entry.file_name() == NODE_MODULES {
+                    vec![entry.path()]
+                } else {
+                    Self::retrieve_node_modules_dirs(&entry.path())
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the directory is kept, or is (or contains) a volume
+    fn is_protected_dir(&self, dir: &Path, volumes: &[PathBuf]) -> bool {
+        self.exclusion.is_kept(dir)
+            || volumes
+                .iter()
+                .any(|volume| volume.starts_with(dir) || dir.starts_with(volume))
+    }
+
+    /// Removes the empty subdirectories of the directory, and returns whether it is empty
+    fn remove_empty_subdirs(&self, dir: &Path, volumes: &[PathBuf]) -> bool {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return false;
+        };
+
+        let mut is_empty = true;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_removed = entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+                && self.remove_empty_subdirs(&path, volumes)
+                && !self.is_protected_dir(&path, volumes)
+                && fs::remove_dir(&path).is_ok();
+
+            is_empty &= is_removed;
         }
+
+        is_empty
     }
 
-    /// Deletes pnpm cache
-    fn delete_pnpm_cache(&self) {
-        Self::delete_path(
-            &self
-                .configurations
-                .home_location
-                .join(".pnpm-state")
-                .clone(),
-        );
-        Self::delete_path(
-            &self
-                .configurations
-                .home_location
-                .join(".local")
-                .join("share")
-                .join("pnpm")
-                .clone(),
+    /// Removes the empty directories of the `node_modules` trees and of `--empty-dirs-location`
+    fn remove_empty_dirs(&self) {
+        let configurations = self.configurations;
+        let canonicalize = |path: &PathBuf| path.canonicalize().unwrap_or(path.clone());
+
+        let volumes: Vec<PathBuf> = configurations.volumes.iter().map(canonicalize).collect();
+        let mut dirs = Self::retrieve_node_modules_dirs(&configurations.project_root_location);
+        dirs.push(configurations.node_modules_location.clone());
+        dirs.extend(
+            configurations
+                .empty_dirs_location
+                .iter()
+                .map(|path| configurations.project_root_location.join(path)),
         );
+
+        let mut dirs: Vec<PathBuf> = dirs
+            .iter()
+            .filter(|dir| dir.is_dir())
+            .map(canonicalize)
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+
+        for dir in dirs {
+            self.remove_empty_subdirs(&dir, &volumes);
+        }
+        println!("Removed empty directories");
     }
 
-    /// Deletes lock files
-    fn delete_lock_files(self) {
-        Self::delete_path(
-            &self
-                .configurations
-                .project_root_location
-                .join("package-lock.json")
-                .clone(),
-        );
-        Self::delete_path(
-            &self
-                .configurations
-                .project_root_location
-                .join("yarn.lock")
-                .clone(),
-        );
-        Self::delete_path(
-            &self
-                .configurations
-                .project_root_location
-                .join("pnpm-lock.yaml")
-                .clone(),
-        );
+    /// Returns the enabled package-manager caches and lock files that exist
+    ///
+    /// The project `.yarn/cache` is kept when Plug'n'Play loads the packages from it.
+    fn retrieve_cleanup_targets(&self) -> Vec<PathBuf> {
+        let configurations = self.configurations;
+        let home = &configurations.home_location;
+        let project_root = &configurations.project_root_location;
+        let uses_pnp = project_root.join(".pnp.cjs").is_file();
+
+        [
+            (configurations.delete_npm_cache, home, NPM_CACHE_ITEMS),
+            (configurations.delete_pnpm_cache, home, PNPM_CACHE_ITEMS),
+            (configurations.delete_yarn_cache, home, YARN_CACHE_ITEMS),
+            (
+                configurations.delete_yarn_cache && !uses_pnp,
+                project_root,
+                YARN_PROJECT_CACHE_ITEMS,
+            ),
+            (configurations.delete_bun_cache, home, BUN_CACHE_ITEMS),
+            (configurations.delete_lock_files, project_root, LOCK_FILES),
+        ]
+        .iter()
+        .filter(|(is_enabled, _, _)| *is_enabled)
+        .flat_map(|(_, base, items)| items.iter().map(|item| base.join(item)))
+        .filter(|path| path.exists())
+        .collect()
     }
 
     /// Cleans up the `node_modules` directory
@@ -194,9 +450,9 @@ impl<'a> Cleaner<'a> {
             Self::delete_path(path);
         }
         self.remove_empty_dirs();
-        Self::delete_path(&self.configurations.home_location.join(".npm").clone());
-        self.delete_pnpm_cache();
-        self.delete_lock_files();
+        for path in self.retrieve_cleanup_targets() {
+            Self::delete_path(&path);
+        }
     }
 }
 
@@ -222,7 +478,47 @@ mod tests {
             entry_point_location: vec!["tests/index.js".into()],
             ..Default::default()
         };
-        Cleaner::from_module_graph(configurations, &HashSet::new()).remove_empty_dirs();
+        Cleaner::from_module_graph(configurations, &[], &HashSet::new()).remove_empty_dirs();
+    }
+
+    #[test]
+    fn test_remove_empty_dirs_scope() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().canonicalize().unwrap();
+
+        for dir in [
+            "uploads",
+            "logs/archive",
+            "node_modules/fastify/test/fixtures",
+            "node_modules/fastify/cache",
+            "node_modules/fastify/data",
+            "packages/api/node_modules/busboy/deps",
+        ] {
+            temp.child(dir).create_dir_all().unwrap();
+        }
+        temp.child("node_modules/fastify/fastify.js")
+            .touch()
+            .unwrap();
+
+        let configurations = &Cli {
+            project_root_location: path.clone(),
+            node_modules_location: path.join("node_modules"),
+            keep: Some(vec!["node_modules/fastify/cache".into()]),
+            empty_dirs_location: vec!["logs".into()],
+            volumes: vec![path.join("node_modules/fastify/data")],
+            ..Default::default()
+        };
+
+        Cleaner::new(configurations, &[], Vec::new()).remove_empty_dirs();
+
+        assert!(path.join("uploads").exists());
+        assert!(path.join("logs").exists());
+        assert!(!path.join("logs/archive").exists());
+        assert!(!path.join("node_modules/fastify/test").exists());
+        assert!(path.join("node_modules/fastify/cache").exists());
+        assert!(path.join("node_modules/fastify/data").exists());
+        assert!(path.join("packages/api/node_modules").exists());
+        assert!(!path.join("packages/api/node_modules/busboy").exists());
     }
 
     #[test]
@@ -246,6 +542,7 @@ mod tests {
 
         let cleaner = Cleaner::from_module_graph(
             configurations,
+            &[],
             &HashSet::from([legit_esm_path.clone(), legit_path.clone()]),
         );
 
@@ -264,4 +561,264 @@ mod tests {
 
         temp.close().unwrap();
     }
+
+    #[test]
+    fn test_cleanup_targets() {
+        let temp = TempDir::new().unwrap();
+        let home = temp.child("home");
+        let project = temp.child("project");
+
+        for file in [
+            ".npm/_cacache/index",
+            ".cache/yarn/v6/index",
+            ".bun/install/cache/index",
+        ] {
+            home.child(file).touch().unwrap();
+        }
+        for file in ["package-lock.json", "bun.lockb", ".yarn/cache/lodash.zip"] {
+            project.child(file).touch().unwrap();
+        }
+
+        let configurations = &Cli {
+            home_location: home.to_path_buf(),
+            project_root_location: project.to_path_buf(),
+            ..Default::default()
+        };
+
+        assert!(Cleaner::new(configurations, &[], Vec::new())
+            .retrieve_cleanup_targets()
+            .is_empty());
+
+        let configurations = &Cli {
+            home_location: home.to_path_buf(),
+            project_root_location: project.to_path_buf(),
+            delete_yarn_cache: true,
+            delete_lock_files: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Cleaner::new(configurations, &[], Vec::new()).retrieve_cleanup_targets(),
+            vec![
+                home.join(".cache/yarn"),
+                project.join(".yarn/cache"),
+                project.join("package-lock.json"),
+                project.join("bun.lockb"),
+            ]
+        );
+
+        project.child(".pnp.cjs").touch().unwrap();
+
+        assert!(!Cleaner::new(configurations, &[], Vec::new())
+            .retrieve_cleanup_targets()
+            .contains(&project.join(".yarn/cache")));
+    }
+
+    #[test]
+    fn test_extend_platform_garbage() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().canonicalize().unwrap();
+        let sharp = path.join("node_modules/sharp");
+
+        for file in [
+            "vendor/darwin-arm64/libvips.dylib",
+            "vendor/win32-x64/libvips.dll",
+        ] {
+            temp.child("node_modules/sharp")
+                .child(file)
+                .touch()
+                .unwrap();
+        }
+
+        let configurations = &Cli {
+            project_root_location: path.clone(),
+            keep: Some(vec!["node_modules/sharp/vendor/win32-x64".into()]),
+            ..Default::default()
+        };
+        let applied_presets = [(
+            sharp.clone(),
+            Preset {
+                package: "sharp".into(),
+                keep: vec!["vendor/**/*.*".into()],
+                garbage: Vec::new(),
+            },
+        )];
+        let platform_garbage = vec![
+            sharp.join("vendor/darwin-arm64"),
+            sharp.join("vendor/win32-x64"),
+        ];
+
+        let mut cleaner = Cleaner::new(configurations, &applied_presets, Vec::new());
+        cleaner.extend_garbage(platform_garbage.clone());
+
+        assert!(cleaner.retrieve_garbage().is_empty());
+
+        cleaner.extend_platform_garbage(platform_garbage);
+
+        assert_eq!(
+            cleaner.retrieve_garbage(),
+            &vec![sharp.join("vendor/darwin-arm64")]
+        );
+    }
+
+    #[test]
+    fn test_remove_unreached_packages() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().canonicalize().unwrap();
+        let node_modules = path.join("node_modules");
+
+        for file in [
+            "fastify/package.json",
+            "fastify/fastify.js",
+            "fastify/lib/unused.js",
+            "fastify/esm/package.json",
+            "busboy/package.json",
+            "busboy/lib/main.js",
+            "busboy/node_modules/streamsearch/package.json",
+            "@fastify/ajv-compiler/package.json",
+            "@fastify/ajv-compiler/index.js",
+            "outer/package.json",
+            "outer/index.js",
+            "outer/node_modules/inner/package.json",
+            "outer/node_modules/inner/index.js",
+        ] {
+            temp.child("node_modules").child(file).touch().unwrap();
+        }
+
+        let configurations = &Cli {
+            project_root_location: path.clone(),
+            ..Default::default()
+        };
+
+        let cleaner = Cleaner::from_module_graph(
+            configurations,
+            &[],
+            &HashSet::from([
+                node_modules.join("fastify/fastify.js"),
+                node_modules.join("outer/node_modules/inner/index.js"),
+            ]),
+        );
+
+        let mut garbage = cleaner.retrieve_garbage().clone();
+        garbage.sort();
+
+        assert_eq!(
+            garbage,
+            vec![
+                node_modules.join("@fastify/ajv-compiler"),
+                node_modules.join("busboy"),
+                node_modules.join("fastify/lib/unused.js"),
+                node_modules.join("outer/index.js"),
+                node_modules.join("outer/package.json"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keep_referenced_static_garbage() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().canonicalize().unwrap();
+        let node_modules = path.join("node_modules");
+
+        temp.child("node_modules/test/package.json")
+            .write_str(r#"{"name":"test","main":"lib/index"}"#)
+            .unwrap();
+        temp.child("node_modules/test/lib/index.js")
+            .write_str("module.exports = require('./a');")
+            .unwrap();
+        temp.child("node_modules/test/lib/a.js").touch().unwrap();
+        temp.child("node_modules/test/data/table.json")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/tiny/package.json")
+            .write_str(r#"{"name":"tiny","exports":{"types":"./dist/tiny.d.ts","default":"./dist/tiny.min.js"},"bin":{"tiny":"bin/cli.js"}}"#)
+            .unwrap();
+        temp.child("node_modules/tiny/dist/tiny.d.ts")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/@types/node/package.json")
+            .write_str(r#"{"name":"@types/node","exports":{".":{"types":"./index.d.ts"}}}"#)
+            .unwrap();
+        temp.child("node_modules/@types/node/index.d.ts")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/tiny/dist/tiny.min.js")
+            .write_str("module.exports = require('./helper.min.js');")
+            .unwrap();
+        temp.child("node_modules/tiny/dist/helper.min.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/tiny/dist/tiny.min.mjs")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/tiny/README.md").touch().unwrap();
+
+        let configurations = &Cli {
+            node_modules_location: node_modules.clone(),
+            ..Default::default()
+        };
+
+        let mut garbage = Cleaner::from_static_garbage(configurations, &[])
+            .retrieve_garbage()
+            .clone();
+        garbage.sort();
+
+        assert_eq!(
+            garbage,
+            vec![
+                node_modules.join("@types"),
+                node_modules.join("@types/node/index.d.ts"),
+                node_modules.join("tiny/README.md"),
+                node_modules.join("tiny/dist/tiny.d.ts"),
+                node_modules.join("tiny/dist/tiny.min.mjs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_static_categories() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().canonicalize().unwrap();
+        let fastify = path.join("node_modules/fastify");
+
+        for file in [
+            "fastify.d.ts",
+            "fastify.js.map",
+            "README.md",
+            "test/index.js",
+        ] {
+            temp.child("node_modules/fastify")
+                .child(file)
+                .touch()
+                .unwrap();
+        }
+
+        let configurations = &Cli {
+            node_modules_location: path.join("node_modules"),
+            skip_categories: vec![StaticCategory::Typings, StaticCategory::Sourcemaps],
+            ..Default::default()
+        };
+
+        let mut garbage = Cleaner::from_static_garbage(configurations, &[])
+            .retrieve_garbage()
+            .clone();
+        garbage.sort();
+
+        assert_eq!(
+            garbage,
+            vec![fastify.join("README.md"), fastify.join("test")]
+        );
+
+        let configurations = &Cli {
+            node_modules_location: path.join("node_modules"),
+            static_categories: vec![StaticCategory::Typings, StaticCategory::Tests],
+            skip_categories: vec![StaticCategory::Tests],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Cleaner::from_static_garbage(configurations, &[]).retrieve_garbage(),
+            &vec![fastify.join("fastify.d.ts")]
+        );
+    }
 }
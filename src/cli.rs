@@ -1,35 +1,104 @@
+use std::collections::{HashMap, HashSet};
+
 use nmt::module_graph::Visitor;
 use nmt::{
     cleaner::Cleaner,
     configurations::{Cli, Strategy},
-    minifier,
+    locales, lockfile, minifier, module_format, native, nft, package, platform, presets, stripper,
+    trace,
 };
 
 fn main() {
     let configurations = &Cli::new();
 
-    let cleaner = match configurations.strategy {
+    if let Some(trace_hook_location) = &configurations.trace_hook_location {
+        trace::write_trace_hook(trace_hook_location);
+        return;
+    }
+
+    let applied_presets = presets::retrieve_applied_presets(configurations);
+    presets::report_presets(&applied_presets);
+
+    let mut module_graph = HashSet::new();
+    let mut package_formats = HashMap::new();
+
+    let mut cleaner = match configurations.strategy {
         Strategy::Ast => {
-            let module_graph = Visitor::new(configurations).run();
+            let mut visitor = Visitor::new(configurations, &applied_presets);
+            module_graph = visitor.run();
+            package_formats = visitor.package_formats();
+            visitor.report_hazards();
 
-            Cleaner::from_module_graph(configurations, &module_graph)
+            Cleaner::from_module_graph(configurations, &applied_presets, &module_graph)
+        }
+        Strategy::Static => Cleaner::from_static_garbage(configurations, &applied_presets),
+        Strategy::Trace => {
+            module_graph = trace::retrieve_trace_paths(configurations);
+
+            if configurations.trace_with_ast {
+                let mut visitor = Visitor::new(configurations, &applied_presets);
+                module_graph.extend(visitor.run());
+                package_formats = visitor.package_formats();
+                visitor.report_hazards();
+            }
+
+            Cleaner::from_module_graph(configurations, &applied_presets, &module_graph)
+        }
+        Strategy::Nft => {
+            module_graph = nft::retrieve_nft_paths(configurations);
+
+            Cleaner::from_module_graph(configurations, &applied_presets, &module_graph)
         }
-        Strategy::Static => Cleaner::from_static_garbage(configurations),
     };
 
+    cleaner.extend_garbage(lockfile::retrieve_dev_garbage(configurations));
+    cleaner.extend_platform_garbage(platform::retrieve_platform_garbage(configurations));
+    cleaner.extend_garbage(native::retrieve_native_garbage(configurations));
+    cleaner.extend_garbage(locales::retrieve_locale_garbage(configurations));
+    cleaner.extend_garbage(module_format::retrieve_format_garbage(
+        configurations,
+        &package_formats,
+        &module_graph,
+    ));
+
     if configurations.dry_run {
         println!("Dry run. These are the paths that would be removed:");
         cleaner
             .retrieve_garbage()
             .iter()
             .for_each(|path| println!("{}", path.display()));
+    } else if configurations.rewrite_exports {
+        let removed_paths = cleaner.retrieve_garbage().clone();
+
+        cleaner.clean();
+        package::rewrite_manifests(&removed_paths);
     } else {
         cleaner.clean();
     }
 
+    if configurations.rewrite_exports && configurations.dry_run {
+        println!("Dry run. Manifests rewrite skipped");
+    }
+
+    if configurations.slim_manifests {
+        if configurations.dry_run {
+            println!("Dry run. Manifests slimming skipped");
+        } else {
+            package::slim_manifests(configurations);
+        }
+    }
+
     if configurations.minify {
         minifier::minify(configurations);
     } else {
         println!("Minification skipped");
     }
+
+    if configurations.strip_native {
+        if configurations.dry_run {
+            println!("Dry run. Native addons stripping skipped");
+        } else {
+            stripper::strip(configurations);
+        }
+    }
 }
@@ -5,7 +5,7 @@ use std::path::{Path, PathBuf};
 use clap::Parser;
 use dirs;
 
-use crate::glob::retrieve_glob_paths;
+use crate::{exclusion::Exclusion, glob::retrieve_glob_paths, presets::Preset};
 
 const PROJECT_ROOT_LOCATION: &str = "PROJECT_ROOT_LOCATION";
 const ENTRY_POINT_LOCATION: &str = "ENTRY_POINT_LOCATION";
@@ -17,6 +17,36 @@ const DRY_RUN: &str = "DRY_RUN";
 const SOURCE_IMAGE: &str = "SOURCE_IMAGE";
 const DESTINATION_IMAGE: &str = "DESTINATION_IMAGE";
 const MINIFY: &str = "MINIFY";
+const DROP_OPTIONAL: &str = "DROP_OPTIONAL";
+const KEEP_HAZARDOUS: &str = "KEEP_HAZARDOUS";
+const TRACE_LOCATION: &str = "TRACE_LOCATION";
+const TRACE_WITH_AST: &str = "TRACE_WITH_AST";
+const TRACE_HOOK_LOCATION: &str = "TRACE_HOOK_LOCATION";
+const NFT_LOCATION: &str = "NFT_LOCATION";
+const METAFILE_LOCATION: &str = "METAFILE_LOCATION";
+const KEEP_EXPORTS: &str = "KEEP_EXPORTS";
+const KEEP_FORMAT: &str = "KEEP_FORMAT";
+const REWRITE_EXPORTS: &str = "REWRITE_EXPORTS";
+const CONFIG_LOCATION: &str = "CONFIG_LOCATION";
+const STATIC_CATEGORIES: &str = "STATIC_CATEGORIES";
+const SKIP_CATEGORIES: &str = "SKIP_CATEGORIES";
+const NO_PRESETS: &str = "NO_PRESETS";
+const PLATFORM: &str = "PLATFORM";
+const ARCH: &str = "ARCH";
+const LIBC: &str = "LIBC";
+const PRUNE_NATIVE: &str = "PRUNE_NATIVE";
+const STRIP_NATIVE: &str = "STRIP_NATIVE";
+const LOCALES: &str = "LOCALES";
+const PRUNE_DEV_DEPENDENCIES: &str = "PRUNE_DEV_DEPENDENCIES";
+const DELETE_NPM_CACHE: &str = "DELETE_NPM_CACHE";
+const DELETE_PNPM_CACHE: &str = "DELETE_PNPM_CACHE";
+const DELETE_YARN_CACHE: &str = "DELETE_YARN_CACHE";
+const DELETE_BUN_CACHE: &str = "DELETE_BUN_CACHE";
+const DELETE_LOCK_FILES: &str = "DELETE_LOCK_FILES";
+const KEEP_CACHES: &str = "KEEP_CACHES";
+const EMPTY_DIRS_LOCATION: &str = "EMPTY_DIRS_LOCATION";
+const VOLUMES: &str = "VOLUMES";
+const SLIM_MANIFESTS: &str = "SLIM_MANIFESTS";
 const DEFAULT_IMAGE_NAME: &str = "hello-world";
 const DEFAULT_HOME_DIR: &str = "~";
 const DEFAULT_ROOT_LOCATION: &str = ".";
@@ -28,6 +58,50 @@ pub enum Strategy {
     #[default]
     Static,
     Ast,
+    Trace,
+    Nft,
+}
+
+/// Category of the built-in garbage patterns of the static strategy
+#[derive(strum::Display, strum::EnumString, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum StaticCategory {
+    /// Documentation, examples and licenses
+    Docs,
+    /// Tests, benchmarks and their configuration
+    Tests,
+    /// Type definitions
+    Typings,
+    /// Source maps
+    Sourcemaps,
+    /// Lint and editor configuration
+    Lint,
+    /// CI configuration
+    Ci,
+    /// Build configuration and browser builds
+    Build,
+    /// Package manager metadata
+    PackageManager,
+    /// Minified duplicates
+    Minified,
+}
+
+/// C standard library of the Linux target
+#[derive(strum::Display, strum::EnumString, Debug, Clone, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Libc {
+    Glibc,
+    Musl,
+}
+
+/// Module format to keep in dual CJS/ESM packages
+#[derive(strum::Display, strum::EnumString, Debug, Clone, Default)]
+pub enum ModuleFormat {
+    /// The format loaded by the module graph
+    #[default]
+    Auto,
+    Cjs,
+    Esm,
 }
 
 /// Configuration for the CLI
@@ -57,11 +131,98 @@ pub struct Cli {
     /// Whether to minify JS files
     #[arg(short, long, default_value_t = false, env = MINIFY)]
     pub minify: bool,
-    /// A list of files to ignore
+    /// A list of files to ignore (patterns starting with `!` re-include the files kept by a previous pattern)
     #[arg(short, long, env = KEEP, value_delimiter = ',')]
     pub keep: Option<Vec<String>>,
     #[arg(short = 'S', long, default_value_t = Strategy::Static, env = STRATEGY)]
     pub strategy: Strategy,
+    /// Whether to remove packages that are only loaded as optional dependencies
+    #[arg(long, default_value_t = false, env = DROP_OPTIONAL)]
+    pub drop_optional: bool,
+    /// Whether to keep packages with dynamic code (like `eval` or `require(variable)`) whole
+    #[arg(long, default_value_t = false, env = KEEP_HAZARDOUS)]
+    pub keep_hazardous: bool,
+    /// Paths to the runtime traces (`NODE_V8_COVERAGE` directories or load trace files)
+    #[arg(long, env = TRACE_LOCATION, value_delimiter = ',')]
+    pub trace_location: Vec<PathBuf>,
+    /// Whether to combine the runtime traces with the AST module graph
+    #[arg(long, default_value_t = false, env = TRACE_WITH_AST)]
+    pub trace_with_ast: bool,
+    /// Path where to write the preload hook that records the loaded files
+    #[arg(long, env = TRACE_HOOK_LOCATION)]
+    pub trace_hook_location: Option<PathBuf>,
+    /// Path to the directory that contains the `.nft.json` files, `node_modules` excluded [default: the project root]
+    #[arg(long, env = NFT_LOCATION)]
+    pub nft_location: Option<PathBuf>,
+    /// Path to an esbuild metafile or webpack stats JSON whose outputs are used as entry points
+    #[arg(long, env = METAFILE_LOCATION)]
+    pub metafile_location: Option<PathBuf>,
+    /// Whether to keep every file referenced by the `exports`, `main`, `module` and `imports` fields of the reached packages
+    #[arg(long, default_value_t = false, env = KEEP_EXPORTS)]
+    pub keep_exports: bool,
+    /// Module format to keep in dual CJS/ESM packages (`Auto` uses the formats loaded by the module graph)
+    #[arg(long, env = KEEP_FORMAT)]
+    pub keep_format: Option<ModuleFormat>,
+    /// Whether to rewrite the `exports`, `main`, `module` and `browser` fields of the kept packages so they only list existing files
+    #[arg(long, default_value_t = false, env = REWRITE_EXPORTS)]
+    pub rewrite_exports: bool,
+    /// Path to the configuration file with the garbage rules [default: `nmt.toml` or the `nmt` key of `package.json`]
+    #[arg(long, env = CONFIG_LOCATION)]
+    pub config_location: Option<PathBuf>,
+    /// Categories of the built-in garbage patterns to apply (docs, tests, typings, sourcemaps, lint, ci, build, package-manager, minified) [default: every category]
+    #[arg(long, env = STATIC_CATEGORIES, value_delimiter = ',')]
+    pub static_categories: Vec<StaticCategory>,
+    /// Categories of the built-in garbage patterns to skip
+    #[arg(long, env = SKIP_CATEGORIES, value_delimiter = ',')]
+    pub skip_categories: Vec<StaticCategory>,
+    /// Whether to skip the built-in keep and garbage rules of well-known packages
+    #[arg(long, default_value_t = false, env = NO_PRESETS)]
+    pub no_presets: bool,
+    /// Platform to keep the binaries of, like `linux` (as in `process.platform`)
+    #[arg(long, env = PLATFORM)]
+    pub platform: Option<String>,
+    /// Architecture to keep the binaries of, like `x64` (as in `process.arch`)
+    #[arg(long, env = ARCH)]
+    pub arch: Option<String>,
+    /// C standard library to keep the Linux binaries of (glibc or musl)
+    #[arg(long, env = LIBC)]
+    pub libc: Option<Libc>,
+    /// Whether to remove the build intermediates and C/C++ sources of native packages, keeping their `.node` addons
+    #[arg(long, default_value_t = false, env = PRUNE_NATIVE)]
+    pub prune_native: bool,
+    /// Whether to strip the debug sections of the ELF `.node` addons
+    #[arg(long, default_value_t = false, env = STRIP_NATIVE)]
+    pub strip_native: bool,
+    /// Locales to keep in the locale directories of known packages (like `moment` and `dayjs`), removing the other ones and the unused `moment-timezone` data [default: every locale]
+    #[arg(long, env = LOCALES, value_delimiter = ',')]
+    pub locales: Vec<String>,
+    /// Whether to remove the packages that the lockfile only reaches through `devDependencies`
+    #[arg(long, default_value_t = false, env = PRUNE_DEV_DEPENDENCIES)]
+    pub prune_dev_dependencies: bool,
+    /// Whether to delete the npm cache (`~/.npm`)
+    #[arg(long, default_value_t = false, env = DELETE_NPM_CACHE)]
+    pub delete_npm_cache: bool,
+    /// Whether to delete the pnpm store and state (`~/.local/share/pnpm`, `~/.cache/pnpm` and `~/.pnpm-state`)
+    #[arg(long, default_value_t = false, env = DELETE_PNPM_CACHE)]
+    pub delete_pnpm_cache: bool,
+    /// Whether to delete the yarn caches (`~/.cache/yarn`, `~/.yarn/berry/cache` and the project `.yarn/cache`)
+    #[arg(long, default_value_t = false, env = DELETE_YARN_CACHE)]
+    pub delete_yarn_cache: bool,
+    /// Whether to delete the bun cache (`~/.bun/install/cache`)
+    #[arg(long, default_value_t = false, env = DELETE_BUN_CACHE)]
+    pub delete_bun_cache: bool,
+    /// Whether to delete the project lock files (`package-lock.json`, `npm-shrinkwrap.json`, `yarn.lock`, `pnpm-lock.yaml`, `bun.lock` and `bun.lockb`)
+    #[arg(long, default_value_t = false, env = DELETE_LOCK_FILES)]
+    pub delete_lock_files: bool,
+    /// Paths to the directories whose empty subdirectories are removed, besides the `node_modules` ones
+    #[arg(long, env = EMPTY_DIRS_LOCATION, value_delimiter = ',')]
+    pub empty_dirs_location: Vec<PathBuf>,
+    /// Paths to the volumes of the image, which are never removed
+    #[arg(long, env = VOLUMES, value_delimiter = ',')]
+    pub volumes: Vec<PathBuf>,
+    /// Whether to remove the `package.json` fields that are not used at runtime (like `scripts`, `readme` and `devDependencies`)
+    #[arg(long, default_value_t = false, env = SLIM_MANIFESTS)]
+    pub slim_manifests: bool,
 }
 
 /// Configuration for the Docker image
@@ -76,6 +237,9 @@ pub struct Docker {
     /// The destination image
     #[arg(short = 'D', long, default_value = "", env = DESTINATION_IMAGE)]
     pub destination_image: String,
+    /// Whether to keep the package-manager caches and lock files, which are deleted by default
+    #[arg(long, default_value_t = false, env = KEEP_CACHES)]
+    pub keep_caches: bool,
 }
 
 impl Cli {
@@ -120,23 +284,14 @@ impl Cli {
             .expect("Failed to canonicalize node modules location");
     }
 
-    pub fn keep_files(&self) -> Vec<PathBuf> {
-        let globs: Vec<String> = self
-            .keep
-            .as_ref()
-            .unwrap_or(&Vec::new())
-            .iter()
-            .map(|keep_pattern| {
-                self.project_root_location
-                    .join(keep_pattern)
-                    .display()
-                    .to_string()
-            })
-            .collect();
+    /// Returns the kept files, without the ones re-included by a `!` pattern
+    pub fn keep_files(&self, applied_presets: &[(PathBuf, Preset)]) -> Vec<PathBuf> {
+        let exclusion = Exclusion::from_configurations(self, applied_presets);
 
-        retrieve_glob_paths(globs)
+        retrieve_glob_paths(exclusion.keep_patterns())
             .into_iter()
             .filter(|path| path.is_file())
+            .filter(|path| exclusion.is_kept(path))
             .collect()
     }
 
@@ -176,16 +331,114 @@ impl Cli {
         )
         .as_str();
 
-        [(DRY_RUN, self.dry_run), (MINIFY, self.minify)]
-            .iter()
-            .filter(|(_, value)| *value)
-            .for_each(|(env_name, value)| {
-                env += format!(
-                    "ENV {env_name}={value}
+        [
+            (DRY_RUN, self.dry_run),
+            (MINIFY, self.minify),
+            (DROP_OPTIONAL, self.drop_optional),
+            (KEEP_HAZARDOUS, self.keep_hazardous),
+            (TRACE_WITH_AST, self.trace_with_ast),
+            (KEEP_EXPORTS, self.keep_exports),
+            (REWRITE_EXPORTS, self.rewrite_exports),
+            (NO_PRESETS, self.no_presets),
+            (PRUNE_NATIVE, self.prune_native),
+            (STRIP_NATIVE, self.strip_native),
+            (PRUNE_DEV_DEPENDENCIES, self.prune_dev_dependencies),
+            (DELETE_NPM_CACHE, self.delete_npm_cache),
+            (DELETE_PNPM_CACHE, self.delete_pnpm_cache),
+            (DELETE_YARN_CACHE, self.delete_yarn_cache),
+            (DELETE_BUN_CACHE, self.delete_bun_cache),
+            (DELETE_LOCK_FILES, self.delete_lock_files),
+            (SLIM_MANIFESTS, self.slim_manifests),
+        ]
+        .iter()
+        .filter(|(_, value)| *value)
+        .for_each(|(env_name, value)| {
+            env += format!(
+                "ENV {env_name}={value}
 "
-                )
-                .as_str();
-            });
+            )
+            .as_str();
+        });
+
+        [
+            (
+                TRACE_LOCATION,
+                self.trace_location
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<String>>(),
+            ),
+            (
+                NFT_LOCATION,
+                self.nft_location
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<String>>(),
+            ),
+            (
+                METAFILE_LOCATION,
+                self.metafile_location
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<String>>(),
+            ),
+            (
+                EMPTY_DIRS_LOCATION,
+                self.empty_dirs_location
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<String>>(),
+            ),
+            (
+                VOLUMES,
+                self.volumes
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<String>>(),
+            ),
+            (PLATFORM, self.platform.iter().cloned().collect()),
+            (ARCH, self.arch.iter().cloned().collect()),
+            (LIBC, self.libc.iter().map(ToString::to_string).collect()),
+            (LOCALES, self.locales.clone()),
+            (
+                STATIC_CATEGORIES,
+                self.static_categories
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>(),
+            ),
+            (
+                SKIP_CATEGORIES,
+                self.skip_categories
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>(),
+            ),
+            (
+                CONFIG_LOCATION,
+                self.config_location
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<String>>(),
+            ),
+            (
+                KEEP_FORMAT,
+                self.keep_format
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>(),
+            ),
+        ]
+        .iter()
+        .filter(|(_, values)| !values.is_empty())
+        .for_each(|(env_name, values)| {
+            env += format!(
+                "ENV {env_name}={:?}
+",
+                values.join(",")
+            )
+            .as_str();
+        });
 
         if let Some(keep) = &self.keep {
             env += format!("ENV {}={:?}", KEEP, keep.join(",")).as_str();
@@ -207,11 +460,23 @@ impl Docker {
         }
     }
 
+    /// Deletes the package-manager caches and lock files, unless they are kept
+    pub fn default_cleanup_targets(&mut self) {
+        if !self.keep_caches {
+            self.cli.delete_npm_cache = true;
+            self.cli.delete_pnpm_cache = true;
+            self.cli.delete_yarn_cache = true;
+            self.cli.delete_bun_cache = true;
+            self.cli.delete_lock_files = true;
+        }
+    }
+
     /// Returns a new configuration
     pub fn new() -> Self {
         let mut docker_configurations = Self::parse();
 
         docker_configurations.default_destination_image();
+        docker_configurations.default_cleanup_targets();
 
         docker_configurations
     }
@@ -235,6 +500,7 @@ mod tests {
 
         env::remove_var(SOURCE_IMAGE);
         env::remove_var(DESTINATION_IMAGE);
+        env::remove_var(KEEP_CACHES);
     }
 
     #[test]
@@ -344,5 +610,17 @@ mod tests {
             configurations.destination_image,
             format!("{DEFAULT_IMAGE_NAME}:trimmed")
         );
+        assert!(configurations.cli.delete_npm_cache && configurations.cli.delete_lock_files);
+    }
+
+    #[test]
+    fn test_docker_keep_caches() {
+        clean_docker_env();
+        env::set_var(KEEP_CACHES, "true");
+
+        let configurations = Docker::new();
+
+        assert!(!configurations.cli.delete_npm_cache);
+        assert!(!configurations.cli.delete_lock_files);
     }
 }
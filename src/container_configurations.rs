@@ -15,6 +15,8 @@ pub struct ContainerConfigurations {
     pub user: Option<String>,
     /// ENV instruction
     pub env: Option<String>,
+    /// Mount points of the VOLUME instruction
+    pub volumes: Vec<String>,
 }
 
 impl ContainerConfigurations {
@@ -89,6 +91,7 @@ impl ContainerConfigurations {
                 }
                 None => None,
             },
+            volumes: container_config.volumes.unwrap_or_default(),
         }
     }
 
@@ -101,6 +104,7 @@ impl ContainerConfigurations {
             self.user.clone(),
             self.env.clone(),
             self.health_check.clone(),
+            (!self.volumes.is_empty()).then(|| format!("VOLUME {:?}", self.volumes)),
         ]
         .iter()
         .filter_map(std::clone::Clone::clone)
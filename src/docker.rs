@@ -7,6 +7,7 @@ use nmt::configurations;
 use nmt::container_configurations::ContainerConfigurations;
 
 use std::io::Write;
+use std::path::PathBuf;
 
 const DOCKERFILE_NAME: &str = "Dockerfile";
 
@@ -118,12 +119,17 @@ async fn retrieve_config(
 
 #[tokio::main]
 async fn main() -> Result<(), bollard::errors::Error> {
-    let configurations = &configurations::Docker::new();
+    let configurations = &mut configurations::Docker::new();
 
     let docker = Docker::connect_with_socket_defaults().unwrap();
 
     let container_config = retrieve_config(&docker, configurations).await?;
 
+    configurations
+        .cli
+        .volumes
+        .extend(container_config.volumes.iter().map(PathBuf::from));
+
     let dockerfile = create_dockerfile(configurations, &container_config);
 
     let compressed_tar = create_compressed_tar(&dockerfile);
@@ -175,6 +181,7 @@ mod history_tests {
                 env: Some(String::from(
                     "ENV PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin"
                 )),
+                volumes: Vec::new(),
             }
         );
     }
@@ -188,6 +195,7 @@ mod history_tests {
                 source_image: String::from("ilteoood/xdcc-mule"),
                 destination_image: String::from("ilteoood/xdcc-mule"),
                 cli: Cli::new(),
+                keep_caches: false,
             },
         )
         .await
@@ -202,6 +210,7 @@ mod history_tests {
                 health_check: None,
                 user: None,
                 env: Some(String::from("ENV PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin\nENV NODE_VERSION=24.15.0\nENV YARN_VERSION=1.22.22")),
+                volumes: Vec::new(),
             }
         );
     }
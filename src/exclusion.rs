@@ -0,0 +1,204 @@
+//! Exclusion of the kept paths from the garbage
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob::{MatchOptions, Pattern};
+
+use crate::{
+    configurations::Cli,
+    presets::{self, Preset},
+};
+
+const NEGATION_PREFIX: char = '!';
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+/// Ordered list of keep patterns, relative to the project root
+///
+/// Patterns starting with `!` re-include the paths kept by a previous pattern. Like in
+/// `.gitignore`, the last matching pattern wins and a pattern that matches a directory applies to
+/// everything inside it.
+#[derive(Debug, Default)]
+pub struct Exclusion {
+    rules: Vec<(Pattern, bool)>,
+}
+
+impl Exclusion {
+    /// # Panics if a pattern is invalid
+    pub fn new(project_root_location: &Path, patterns: &[String]) -> Self {
+        let root = project_root_location
+            .canonicalize()
+            .unwrap_or_else(|_| project_root_location.to_path_buf());
+
+        let rules = patterns
+            .iter()
+            .map(|pattern| {
+                let (pattern, is_kept) = match pattern.strip_prefix(NEGATION_PREFIX) {
+                    Some(pattern) => (pattern, false),
+                    None => (pattern.as_str(), true),
+                };
+                let pattern = root.join(pattern).display().to_string();
+
+                let pattern = Pattern::new(&pattern)
+                    .unwrap_or_else(|_| panic!("Failed to process glob pattern: {pattern}"));
+
+                (pattern, is_kept)
+            })
+            .collect();
+
+        Exclusion { rules }
+    }
+
+    /// Creates the exclusion of the applied presets and of the `--keep` patterns, which come last
+    /// so that they can re-include the files kept by a preset
+    pub fn from_configurations(
+        configurations: &Cli,
+        applied_presets: &[(PathBuf, Preset)],
+    ) -> Self {
+        let patterns = [
+            presets::keep_patterns(applied_presets),
+            configurations.keep.clone().unwrap_or_default(),
+        ]
+        .concat();
+
+        Self::new(&configurations.project_root_location, &patterns)
+    }
+
+    /// Returns the absolute glob patterns that keep paths
+    pub fn keep_patterns(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|(_, is_kept)| *is_kept)
+            .map(|(pattern, _)| pattern.as_str().to_owned())
+            .collect()
+    }
+
+    /// Whether the path, or one of its parent directories, is kept
+    pub fn is_kept(&self, path: &Path) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| {
+                path.ancestors()
+                    .any(|path| pattern.matches_path_with(path, MATCH_OPTIONS))
+            })
+            .is_some_and(|(_, is_kept)| *is_kept)
+    }
+
+    /// Whether the directory contains a kept path
+    fn contains_kept(&self, dir: &Path) -> bool {
+        Self::read_dir(dir)
+            .into_iter()
+            .any(|path| self.is_kept(&path) || (Self::is_dir(&path) && self.contains_kept(&path)))
+    }
+
+    /// Removes the kept paths from the garbage
+    ///
+    /// Directories that contain kept paths are replaced by their other entries, so that the
+    /// rest of the directory is still removed.
+    pub fn apply(&self, garbage: Vec<PathBuf>) -> Vec<PathBuf> {
+        if !self.rules.iter().any(|(_, is_kept)| *is_kept) {
+            return garbage;
+        }
+
+        garbage
+            .into_iter()
+            .flat_map(|path| self.split(path))
+            .collect()
+    }
+
+    fn split(&self, path: PathBuf) -> Vec<PathBuf> {
+        if self.is_kept(&path) {
+            Vec::new()
+        } else if Self::is_dir(&path) && self.contains_kept(&path) {
+            Self::read_dir(&path)
+                .into_iter()
+                .flat_map(|path| self.split(path))
+                .collect()
+        } else {
+            vec![path]
+        }
+    }
+
+    /// Whether the path is a directory, without following symlinks
+    fn is_dir(path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    fn read_dir(dir: &Path) -> Vec<PathBuf> {
+        match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .collect(),
+            Err(err) => {
+                println!("Failed to read directory {}: {}", dir.display(), err);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[test]
+    fn test_is_kept() {
+        let exclusion = Exclusion::new(
+            Path::new("/app"),
+            &[
+                "node_modules/ajv/**".to_owned(),
+                "!node_modules/ajv/test".to_owned(),
+                "node_modules/ajv/test/refs/*.json".to_owned(),
+            ],
+        );
+
+        assert!(exclusion.is_kept(Path::new("/app/node_modules/ajv/lib/ajv.js")));
+        assert!(!exclusion.is_kept(Path::new("/app/node_modules/ajv/test/index.js")));
+        assert!(exclusion.is_kept(Path::new("/app/node_modules/ajv/test/refs/schema.json")));
+        assert!(!exclusion.is_kept(Path::new("/app/node_modules/fastify/fastify.js")));
+    }
+
+    #[test]
+    fn test_apply() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().canonicalize().unwrap();
+
+        temp.child("node_modules/sqlite3/test/support/helper.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/sqlite3/test/support/prepare.db")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/sqlite3/test/other.test.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/sqlite3/docs/README.md")
+            .touch()
+            .unwrap();
+
+        let exclusion = Exclusion::new(&path, &["**/support/*.db".to_owned()]);
+        let sqlite3 = path.join("node_modules/sqlite3");
+
+        let mut garbage = exclusion.apply(vec![sqlite3.join("test"), sqlite3.join("docs")]);
+        garbage.sort();
+
+        assert_eq!(
+            garbage,
+            vec![
+                sqlite3.join("docs"),
+                sqlite3.join("test/other.test.js"),
+                sqlite3.join("test/support/helper.js"),
+            ]
+        );
+    }
+}
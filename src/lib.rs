@@ -1,6 +1,19 @@
+pub mod bundle;
 pub mod cleaner;
 pub mod configurations;
 pub mod container_configurations;
+pub mod exclusion;
 pub mod glob;
+pub mod locales;
+pub mod lockfile;
 pub mod minifier;
+pub mod module_format;
 pub mod module_graph;
+pub mod native;
+pub mod nft;
+pub mod package;
+pub mod platform;
+pub mod presets;
+pub mod project_config;
+pub mod stripper;
+pub mod trace;
@@ -0,0 +1,255 @@
+//! Pruning of the unused locales and timezone data of i18n and date packages
+
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+
+use crate::{
+    configurations::Cli,
+    glob::retrieve_glob_paths,
+    package::{package_name, retrieve_manifests},
+};
+
+/// Locales that are always kept, since packages fall back to them
+static DEFAULT_LOCALES: &[&str] = &["en", "en-us"];
+
+/// Aggregate entries that import every locale of their directory
+static AGGREGATE_ENTRIES: &[&str] = &["index.js", "index.mjs", "index.cjs"];
+
+/// ISO 639-1 languages, followed by the ISO 639-2/3 ones used by CLDR and the known packages
+static LANGUAGES: &[&str] = &[
+    "aa", "ab", "ae", "af", "ak", "am", "an", "ar", "as", "av", "ay", "az", "ba", "be", "bg", "bh",
+    "bi", "bm", "bn", "bo", "br", "bs", "ca", "ce", "ch", "co", "cr", "cs", "cu", "cv", "cy", "da",
+    "de", "dv", "dz", "ee", "el", "en", "eo", "es", "et", "eu", "fa", "ff", "fi", "fj", "fo", "fr",
+    "fy", "ga", "gd", "gl", "gn", "gu", "gv", "ha", "he", "hi", "ho", "hr", "ht", "hu", "hy", "hz",
+    "ia", "id", "ie", "ig", "ii", "ik", "in", "io", "is", "it", "iu", "iw", "ja", "jv", "ka", "kg",
+    "ki", "kj", "kk", "kl", "km", "kn", "ko", "kr", "ks", "ku", "kv", "kw", "ky", "la", "lb", "lg",
+    "li", "ln", "lo", "lt", "lu", "lv", "mg", "mh", "mi", "mk", "ml", "mn", "mo", "mr", "ms", "mt",
+    "my", "na", "nb", "nd", "ne", "ng", "nl", "nn", "no", "nr", "nv", "ny", "oc", "oj", "om", "or",
+    "os", "pa", "pi", "pl", "ps", "pt", "qu", "rm", "rn", "ro", "ru", "rw", "sa", "sc", "sd", "se",
+    "sg", "sh", "si", "sk", "sl", "sm", "sn", "so", "sq", "sr", "ss", "st", "su", "sv", "sw", "ta",
+    "te", "tg", "th", "ti", "tk", "tl", "tn", "to", "tr", "ts", "tt", "tw", "ty", "ug", "uk", "ur",
+    "uz", "ve", "vi", "vo", "wa", "wo", "xh", "yi", "yo", "za", "zh", "zu", "agq", "ast", "bas",
+    "bem", "bez", "brx", "byn", "ccp", "ceb", "cgg", "chr", "ckb", "dav", "dje", "doi", "dsb",
+    "dua", "dyo", "ebu", "ewo", "fil", "fur", "gom", "gsw", "guz", "haw", "hsb", "jgo", "jmc",
+    "kab", "kam", "kde", "kea", "kgp", "khq", "kkj", "kln", "kok", "ksb", "ksf", "ksh", "lag",
+    "lkt", "lrc", "luo", "luy", "mai", "mas", "mer", "mfe", "mgh", "mgo", "mni", "mua", "mzn",
+    "naq", "nds", "nmg", "nnh", "nqo", "nus", "nyn", "pcm", "rof", "rwk", "sah", "saq", "sat",
+    "sbp", "seh", "ses", "shi", "smn", "syr", "teo", "tet", "tlh", "twq", "tzl", "tzm", "vai",
+    "vun", "wae", "xog", "yav", "yrl", "yue", "zgh",
+];
+
+/// Packages and their directories of locales, relative to the package root
+///
+/// `date-fns` is not listed, since its documented `date-fns/locale` entry imports every locale.
+static LOCALE_DIRS: &[(&str, &[&str])] = &[
+    ("moment", &["locale", "dist/locale"]),
+    ("dayjs", &["locale", "esm/locale"]),
+    ("@formatjs/intl-*", &["locale-data"]),
+    ("intl", &["locale-data/json", "locale-data/jsonp"]),
+    ("i18n-iso-countries", &["langs"]),
+    ("numeral", &["locales"]),
+    ("timeago.js", &["lib/lang", "esm/lang"]),
+    ("flatpickr", &["dist/l10n", "dist/esm/l10n"]),
+];
+
+/// Packages and their timezone data that the main entry never loads, relative to the package
+/// root
+///
+/// `moment-timezone` loads `data/packed/latest.json`, the bundled builds with a subset of the
+/// years and the unpacked data are only meant for browsers and tooling.
+static TIMEZONE_DATA: &[(&str, &[&str])] = &[(
+    "moment-timezone",
+    &[
+        "builds/moment-timezone-with-data-*",
+        "data/meta",
+        "data/unpacked",
+    ],
+)];
+
+/// Returns the lowercase locale of a locale file or directory (like `en_GB.js`), if it is one
+fn locale(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let locale = name
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .replace('_', "-")
+        .to_ascii_lowercase();
+
+    let mut subtags = locale.split('-');
+    let is_language = subtags
+        .next()
+        .is_some_and(|language| LANGUAGES.contains(&language));
+    let are_subtags_valid = subtags.all(|subtag| {
+        (2..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+
+    (is_language && are_subtags_valid).then_some(locale)
+}
+
+/// Whether the locale is one of the kept ones, one of their regional variants or their base
+/// language (like `it` for `it-ch`)
+fn is_kept_locale(locale: &str, kept_locales: &[String]) -> bool {
+    kept_locales.iter().any(|kept_locale| {
+        locale == kept_locale
+            || locale.starts_with(&format!("{kept_locale}-"))
+            || kept_locale.starts_with(&format!("{locale}-"))
+    })
+}
+
+/// Whether an aggregate entry of the locale directory imports every locale, so that none can be
+/// removed
+fn has_aggregate_entry(locale_dir: &Path) -> bool {
+    AGGREGATE_ENTRIES
+        .iter()
+        .any(|entry| locale_dir.join(entry).is_file())
+}
+
+/// Retrieves the locale files and directories of the known packages that are not listed in
+/// `--locales`, along with the timezone data they never load
+pub fn retrieve_locale_garbage(configurations: &Cli) -> Vec<PathBuf> {
+    if configurations.locales.is_empty() {
+        return Vec::new();
+    }
+
+    let kept_locales: Vec<String> = configurations
+        .locales
+        .iter()
+        .map(String::as_str)
+        .chain(DEFAULT_LOCALES.iter().copied())
+        .map(|locale| locale.replace('_', "-").to_ascii_lowercase())
+        .collect();
+
+    let locale_dirs: Vec<(Pattern, &[&str])> = LOCALE_DIRS
+        .iter()
+        .filter_map(|(package, dirs)| Pattern::new(package).ok().map(|pattern| (pattern, *dirs)))
+        .collect();
+
+    let package_dirs: Vec<(String, PathBuf)> =
+        retrieve_manifests(&configurations.node_modules_location)
+            .into_iter()
+            .filter_map(|manifest_path| manifest_path.parent().map(Path::to_path_buf))
+            .map(|dir| (package_name(&dir).unwrap_or_default(), dir))
+            .collect();
+
+    let locale_globs = package_dirs
+        .iter()
+        .flat_map(|(name, dir)| {
+            locale_dirs
+                .iter()
+                .filter(|(pattern, _)| pattern.matches(name))
+                .flat_map(|(_, dirs)| dirs.iter())
+                .map(|locale_dir| dir.join(locale_dir))
+                .filter(|locale_dir| !has_aggregate_entry(locale_dir))
+                .map(|locale_dir| locale_dir.join("*").display().to_string())
+                .collect::<Vec<String>>()
+        })
+        .collect();
+
+    let mut garbage: Vec<PathBuf> = retrieve_glob_paths(locale_globs)
+        .into_iter()
+        .filter(|path| locale(path).is_some_and(|locale| !is_kept_locale(&locale, &kept_locales)))
+        .collect();
+
+    let timezone_globs = package_dirs
+        .iter()
+        .flat_map(|(name, dir)| {
+            TIMEZONE_DATA
+                .iter()
+                .filter(|(package, _)| package == name)
+                .flat_map(|(_, paths)| paths.iter())
+                .map(|path| dir.join(path).display().to_string())
+                .collect::<Vec<String>>()
+        })
+        .collect();
+
+    garbage.extend(retrieve_glob_paths(timezone_globs));
+
+    garbage.sort();
+    garbage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[test]
+    fn test_locale() {
+        assert_eq!(locale(Path::new("locale/en_GB.js")), Some("en-gb".into()));
+        assert_eq!(
+            locale(Path::new("locale/zh-Hant-TW.d.ts")),
+            Some("zh-hant-tw".into())
+        );
+        assert_eq!(locale(Path::new("locale/index.js")), None);
+        assert_eq!(locale(Path::new("locale/_lib")), None);
+        assert_eq!(locale(Path::new("dist/cdn.js")), None);
+        assert_eq!(
+            locale(Path::new("locale/tzm-latn.js")),
+            Some("tzm-latn".into())
+        );
+    }
+
+    #[test]
+    fn test_retrieve_locale_garbage() {
+        let temp = TempDir::new().unwrap();
+        let node_modules = temp.path().canonicalize().unwrap().join("node_modules");
+
+        for file in [
+            "moment/package.json",
+            "moment/locale/it.js",
+            "moment/locale/fr.js",
+            "moment/locale/en-gb.js",
+            "moment/locale/cdn.js",
+            "date-fns/package.json",
+            "date-fns/locale/fr/index.js",
+            "timeago.js/package.json",
+            "timeago.js/lib/lang/index.js",
+            "timeago.js/lib/lang/fr.js",
+            "dayjs/package.json",
+            "dayjs/locale/index.d.ts",
+            "dayjs/locale/fr.js",
+            "@formatjs/intl-pluralrules/package.json",
+            "@formatjs/intl-pluralrules/locale-data/it-CH.js",
+            "@formatjs/intl-pluralrules/locale-data/de.js",
+            "i18n/locale/fr.js",
+            "moment-timezone/package.json",
+            "moment-timezone/index.js",
+            "moment-timezone/data/packed/latest.json",
+            "moment-timezone/data/meta/latest.json",
+            "moment-timezone/builds/moment-timezone.min.js",
+            "moment-timezone/builds/moment-timezone-with-data.js",
+            "moment-timezone/builds/moment-timezone-with-data-10-year-range.js",
+            "moment-timezone/builds/moment-timezone-with-data-1970-2030.min.js",
+        ] {
+            temp.child("node_modules").child(file).touch().unwrap();
+        }
+
+        let configurations = Cli {
+            node_modules_location: node_modules.clone(),
+            locales: vec!["it".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            retrieve_locale_garbage(&configurations),
+            vec![
+                node_modules.join("@formatjs/intl-pluralrules/locale-data/de.js"),
+                node_modules.join("dayjs/locale/fr.js"),
+                node_modules.join("moment/locale/fr.js"),
+                node_modules
+                    .join("moment-timezone/builds/moment-timezone-with-data-10-year-range.js"),
+                node_modules
+                    .join("moment-timezone/builds/moment-timezone-with-data-1970-2030.min.js"),
+                node_modules.join("moment-timezone/data/meta"),
+            ]
+        );
+
+        assert!(retrieve_locale_garbage(&Cli {
+            node_modules_location: node_modules,
+            ..Default::default()
+        })
+        .is_empty());
+    }
+}
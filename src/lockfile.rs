@@ -0,0 +1,878 @@
+//! Pruning of the packages that are only reachable through `devDependencies`, using the lockfile
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    iter::Peekable,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Error};
+use serde_json::Value;
+
+use crate::{
+    configurations::Cli,
+    glob::retrieve_glob_paths,
+    package::{package_root, read_manifest, retrieve_manifests},
+};
+
+const NPM_LOCKFILE: &str = "package-lock.json";
+const PNPM_LOCKFILE: &str = "pnpm-lock.yaml";
+const YARN_LOCKFILE: &str = "yarn.lock";
+const NODE_MODULES: &str = "node_modules";
+
+/// Manifest fields whose packages are needed at runtime
+static PRODUCTION_FIELDS: &[&str] = &["dependencies", "optionalDependencies", "peerDependencies"];
+
+/// Name and version of an installed package
+type Package = (String, String);
+
+/// Resolved dependency graph of a yarn or pnpm lockfile
+///
+/// Dependencies are descriptors, like `lodash@^4.17.0` for yarn or `lodash@4.17.21` for pnpm,
+/// that resolve to a package.
+#[derive(Debug, Default)]
+struct DependencyGraph {
+    descriptors: HashMap<String, Package>,
+    dependencies: HashMap<Package, Vec<String>>,
+    roots: Vec<String>,
+}
+
+impl DependencyGraph {
+    fn add_package(
+        &mut self,
+        descriptors: Vec<String>,
+        package: Package,
+        dependencies: Vec<String>,
+    ) {
+        for descriptor in descriptors {
+            self.descriptors.insert(descriptor, package.clone());
+        }
+        self.dependencies
+            .entry(package)
+            .or_default()
+            .extend(dependencies);
+    }
+
+    /// Resolves a descriptor, or falls back to every package with the same name
+    fn resolve(&self, descriptor: &str) -> Vec<Package> {
+        if let Some(package) = self.descriptors.get(descriptor) {
+            return vec![package.clone()];
+        }
+
+        let name = descriptor_name(descriptor);
+        self.dependencies
+            .keys()
+            .filter(|(package_name, _)| package_name == name)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the packages that the roots never reach
+    fn retrieve_unreached_packages(&self) -> HashSet<Package> {
+        let mut reached: HashSet<Package> = HashSet::new();
+        let mut pending: Vec<Package> = self
+            .roots
+            .iter()
+            .flat_map(|root| self.resolve(root))
+            .collect();
+
+        while let Some(package) = pending.pop() {
+            if let Some(dependencies) = self.dependencies.get(&package) {
+                if reached.insert(package) {
+                    pending.extend(
+                        dependencies
+                            .iter()
+                            .flat_map(|dependency| self.resolve(dependency)),
+                    );
+                }
+            }
+        }
+
+        self.dependencies
+            .keys()
+            .filter(|package| !reached.contains(*package))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Returns the package name of a descriptor, like `@babel/core` for `@babel/core@^7.0.0`
+///
+/// Aliases (like `string-width-cjs@npm:string-width@^4.2.0`) return the aliased package name.
+fn descriptor_name(descriptor: &str) -> &str {
+    let (name, range) = split_at_version(descriptor);
+
+    range
+        .strip_prefix("npm:")
+        .filter(|range| split_at_version(range).0 != *range)
+        .map_or(name, |range| split_at_version(range).0)
+}
+
+/// Splits a `name@version` string at the `@` that follows the (possibly scoped) name
+fn split_at_version(descriptor: &str) -> (&str, &str) {
+    match descriptor
+        .char_indices()
+        .skip(1)
+        .find(|(_, character)| *character == '@')
+    {
+        Some((index, _)) => (&descriptor[..index], &descriptor[index + 1..]),
+        None => (descriptor, ""),
+    }
+}
+
+/// Returns the manifests of the project root and of its workspaces
+fn retrieve_root_manifests(project_root: &Path) -> Vec<Value> {
+    let Some(root_manifest) = read_manifest(&project_root.join("package.json")) else {
+        return Vec::new();
+    };
+
+    let workspaces = match &root_manifest["workspaces"] {
+        Value::Array(workspaces) => workspaces.clone(),
+        workspaces => workspaces["packages"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default(),
+    };
+
+    let workspace_globs = workspaces
+        .iter()
+        .filter_map(Value::as_str)
+        .map(|workspace| {
+            project_root
+                .join(workspace)
+                .join("package.json")
+                .display()
+                .to_string()
+        })
+        .collect();
+
+    let mut manifests: Vec<Value> = retrieve_glob_paths(workspace_globs)
+        .iter()
+        .filter_map(|path| read_manifest(path))
+        .collect();
+    manifests.push(root_manifest);
+    manifests
+}
+
+/// Returns the production dependencies of the manifests as descriptors, normalizing their ranges
+fn retrieve_root_descriptors(
+    project_root: &Path,
+    normalize_range: fn(&str) -> String,
+) -> Vec<String> {
+    retrieve_root_manifests(project_root)
+        .iter()
+        .flat_map(|manifest| {
+            PRODUCTION_FIELDS
+                .iter()
+                .filter_map(|field| manifest[field].as_object())
+                .flatten()
+                .filter_map(|(name, range)| {
+                    range
+                        .as_str()
+                        .map(|range| format!("{name}@{}", normalize_range(range)))
+                })
+                .collect::<Vec<String>>()
+        })
+        .collect()
+}
+
+/// Returns the `node_modules` entries of npm lockfiles v2 and v3 that are flagged as `dev`
+fn retrieve_npm_dev_packages(project_root: &Path, lockfile: &Value) -> Result<Vec<PathBuf>, Error> {
+    let packages = lockfile["packages"].as_object().ok_or_else(|| {
+        anyhow!("missing `packages`, only lockfile versions 2 and 3 are supported")
+    })?;
+
+    Ok(packages
+        .iter()
+        .filter(|(location, _)| {
+            location.starts_with("node_modules/") || location.contains("/node_modules/")
+        })
+        .filter(|(_, package)| {
+            package["dev"].as_bool() == Some(true) && package["link"].as_bool() != Some(true)
+        })
+        .filter_map(|(location, _)| project_root.join(location).canonicalize().ok())
+        .collect())
+}
+
+fn unquote(value: &str) -> String {
+    value.trim().trim_matches('"').to_string()
+}
+
+/// Parses a yarn classic (v1) lockfile
+fn parse_yarn_classic(content: &str) -> DependencyGraph {
+    let mut graph = DependencyGraph::default();
+    let mut descriptors: Vec<String> = Vec::new();
+    let mut version = String::new();
+    let mut dependencies: Vec<String> = Vec::new();
+    let mut is_in_dependencies = false;
+
+    let mut add_entry =
+        |descriptors: &mut Vec<String>, version: &str, dependencies: &mut Vec<String>| {
+            if let Some(descriptor) = descriptors.first() {
+                let name = descriptor_name(descriptor).to_string();
+                graph.add_package(
+                    std::mem::take(descriptors),
+                    (name, version.to_string()),
+                    std::mem::take(dependencies),
+                );
+            }
+        };
+
+    for line in content.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.len() - line.trim_start().len() {
+            0 => {
+                add_entry(&mut descriptors, &version, &mut dependencies);
+                descriptors = line
+                    .trim_end_matches(':')
+                    .split(", ")
+                    .map(unquote)
+                    .collect();
+                is_in_dependencies = false;
+            }
+            2 => {
+                let line = line.trim();
+                is_in_dependencies = line == "dependencies:" || line == "optionalDependencies:";
+
+                if let Some(("version", value)) = line.split_once(' ') {
+                    version = unquote(value);
+                }
+            }
+            _ if is_in_dependencies => {
+                if let Some((name, range)) = line.trim().split_once(' ') {
+                    dependencies.push(format!("{}@{}", unquote(name), unquote(range)));
+                }
+            }
+            _ => {}
+        }
+    }
+    add_entry(&mut descriptors, &version, &mut dependencies);
+
+    graph
+}
+
+/// Mapping of the block YAML subset written by pnpm and yarn berry
+///
+/// Flow collections (like `{integrity: sha512-a}`), sequences and multi-line scalars are not
+/// needed by the dependency graph, so they are kept as raw values or skipped.
+#[derive(Debug, Default)]
+struct YamlNode {
+    value: String,
+    entries: Vec<(String, YamlNode)>,
+}
+
+impl YamlNode {
+    fn parse(content: &str) -> Self {
+        let mut lines = content.lines().filter_map(parse_yaml_line).peekable();
+
+        Self {
+            value: String::new(),
+            entries: parse_yaml_entries(&mut lines, 0),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&YamlNode> {
+        self.entries
+            .iter()
+            .find_map(|(entry_key, node)| (entry_key == key).then_some(node))
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        (!self.value.is_empty()).then_some(self.value.as_str())
+    }
+}
+
+/// Parses a `key: value` line into its indentation, key and value, skipping comments and
+/// sequence items
+fn parse_yaml_line(line: &str) -> Option<(usize, String, String)> {
+    let content = line.trim_start();
+    let indent = line.len() - content.len();
+
+    let (key, value) = match content.chars().next()? {
+        '#' | '-' => return None,
+        quote @ ('"' | '\'') => {
+            let end = content[1..].find(quote)? + 1;
+            (&content[1..end], content[end + 1..].strip_prefix(':')?)
+        }
+        _ => match content.split_once(": ") {
+            Some((key, value)) => (key, value),
+            None => (content.strip_suffix(':')?, ""),
+        },
+    };
+    let value = value.trim();
+    let value = [('"', '"'), ('\'', '\'')]
+        .iter()
+        .find_map(|(start, end)| value.strip_prefix(*start)?.strip_suffix(*end))
+        .unwrap_or(value);
+
+    Some((indent, key.to_string(), value.to_string()))
+}
+
+/// Parses the entries at the given indentation, with their nested entries
+fn parse_yaml_entries(
+    lines: &mut Peekable<impl Iterator<Item = (usize, String, String)>>,
+    indent: usize,
+) -> Vec<(String, YamlNode)> {
+    let mut entries = Vec::new();
+
+    while let Some((line_indent, key, value)) =
+        lines.next_if(|(line_indent, ..)| *line_indent >= indent)
+    {
+        let child_indent = lines
+            .peek()
+            .map(|(child_indent, ..)| *child_indent)
+            .filter(|child_indent| *child_indent > line_indent);
+        let node = YamlNode {
+            value,
+            entries: child_indent
+                .map(|child_indent| parse_yaml_entries(lines, child_indent))
+                .unwrap_or_default(),
+        };
+
+        // Deeper lines without a parent entry belong to a multi-line scalar
+        if line_indent == indent {
+            entries.push((key, node));
+        }
+    }
+
+    entries
+}
+
+/// Yarn berry descriptors default to the `npm:` protocol
+fn normalize_berry_range(range: &str) -> String {
+    if range.contains(':') {
+        range.to_string()
+    } else {
+        format!("npm:{range}")
+    }
+}
+
+/// Parses a yarn berry (v2+) lockfile, skipping the workspaces
+fn parse_yarn_berry(lockfile: &YamlNode) -> Result<DependencyGraph, Error> {
+    if lockfile.entries.is_empty() {
+        return Err(anyhow!("invalid lockfile"));
+    }
+    let mut graph = DependencyGraph::default();
+
+    for (key, entry) in &lockfile.entries {
+        let (Some(resolution), Some(version)) = (
+            entry.get("resolution").and_then(YamlNode::as_str),
+            entry.get("version").and_then(YamlNode::as_str),
+        ) else {
+            continue;
+        };
+
+        if split_at_version(resolution).1.starts_with("workspace:") {
+            continue;
+        }
+
+        let dependencies = entry
+            .get("dependencies")
+            .into_iter()
+            .flat_map(|dependencies| &dependencies.entries)
+            .filter_map(|(name, range)| {
+                Some(format!("{name}@{}", normalize_berry_range(range.as_str()?)))
+            })
+            .collect();
+
+        graph.add_package(
+            key.split(", ").map(str::to_string).collect(),
+            (
+                split_at_version(resolution).0.to_string(),
+                version.to_string(),
+            ),
+            dependencies,
+        );
+    }
+
+    Ok(graph)
+}
+
+/// Returns the `name@version` descriptor of a pnpm version (like `1.0.0(react@18.2.0)`) or
+/// package key (like `/name@1.0.0` or `/name/1.0.0_react@18.2.0` in lockfile v5), or `None` for a
+/// workspace link
+fn pnpm_descriptor(name: &str, version: &str) -> Option<String> {
+    if version.starts_with("link:") {
+        return None;
+    }
+
+    let version = version.split('(').next().unwrap_or_default();
+    let version = version.strip_prefix('/').unwrap_or(version);
+    let strip_peers = |version: &str| version.split('_').next().unwrap_or_default().to_string();
+
+    if version.starts_with(|character: char| character.is_ascii_digit()) {
+        return Some(format!("{name}@{}", strip_peers(version)));
+    }
+
+    if let Some((name, version)) = version
+        .rsplit_once('/')
+        .filter(|(_, version)| version.starts_with(|character: char| character.is_ascii_digit()))
+    {
+        return Some(format!("{name}@{}", strip_peers(version)));
+    }
+
+    (!split_at_version(version).1.is_empty()).then(|| version.to_string())
+}
+
+/// Returns the `name@version` descriptors of a pnpm dependencies map
+fn pnpm_dependencies(node: &YamlNode, fields: &[&str]) -> Vec<String> {
+    fields
+        .iter()
+        .filter_map(|field| node.get(field))
+        .flat_map(|dependencies| &dependencies.entries)
+        .filter_map(|(name, version)| {
+            // Importers of lockfile v6+ list `{ specifier, version }`
+            let version = version
+                .as_str()
+                .or_else(|| version.get("version")?.as_str())?;
+            pnpm_descriptor(name, version)
+        })
+        .collect()
+}
+
+/// Parses a pnpm lockfile (v5, v6 and v9)
+fn parse_pnpm(lockfile: &YamlNode) -> Result<DependencyGraph, Error> {
+    let importers = lockfile
+        .get("importers")
+        .map(|importers| {
+            importers
+                .entries
+                .iter()
+                .map(|(_, importer)| importer)
+                .collect()
+        })
+        .unwrap_or_else(|| vec![lockfile]);
+
+    let mut graph = DependencyGraph {
+        roots: importers
+            .iter()
+            .flat_map(|importer| {
+                pnpm_dependencies(importer, &["dependencies", "optionalDependencies"])
+            })
+            .collect(),
+        ..Default::default()
+    };
+
+    // Lockfile v9 moves the dependencies of the packages to `snapshots`
+    let snapshots = lockfile
+        .get("snapshots")
+        .or_else(|| lockfile.get("packages"))
+        .ok_or_else(|| anyhow!("missing `packages`"))?;
+
+    for (key, snapshot) in &snapshots.entries {
+        let Some(descriptor) = pnpm_descriptor("", key) else {
+            continue;
+        };
+        let (name, version) = split_at_version(&descriptor);
+
+        graph.add_package(
+            vec![descriptor.clone()],
+            (name.to_string(), version.to_string()),
+            pnpm_dependencies(snapshot, &["dependencies", "optionalDependencies"]),
+        );
+    }
+
+    Ok(graph)
+}
+
+/// Returns the installed package directories of the given packages, outermost first
+fn retrieve_installed_packages(
+    node_modules_location: &Path,
+    packages: &HashSet<Package>,
+) -> Vec<PathBuf> {
+    retrieve_manifests(node_modules_location)
+        .into_iter()
+        .filter_map(|manifest_path| {
+            let dir = manifest_path.parent()?.to_path_buf();
+            let manifest = read_manifest(&manifest_path)?;
+            let package = (
+                manifest["name"].as_str()?.to_string(),
+                manifest["version"].as_str()?.to_string(),
+            );
+
+            (package_root(&dir).as_ref() == Some(&dir) && packages.contains(&package))
+                .then_some(dir)
+        })
+        .collect()
+}
+
+/// Returns the symbolic links of `node_modules` (like the ones of pnpm) that point to the garbage
+fn retrieve_garbage_links(node_modules_location: &Path, garbage: &[PathBuf]) -> Vec<PathBuf> {
+    let link_dirs = [
+        node_modules_location.to_path_buf(),
+        node_modules_location.join(".pnpm").join(NODE_MODULES),
+    ];
+    let mut entries: Vec<PathBuf> = Vec::new();
+
+    for dir in link_dirs {
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('@') {
+                entries.extend(
+                    fs::read_dir(&path)
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .map(|entry| entry.path()),
+                );
+            } else {
+                entries.push(path);
+            }
+        }
+    }
+
+    entries
+        .into_iter()
+        .filter(|path| path.is_symlink())
+        .filter(|path| {
+            path.canonicalize()
+                .is_ok_and(|target| garbage.iter().any(|dir| target.starts_with(dir)))
+        })
+        .collect()
+}
+
+/// Reads the lockfile of the project and returns the packages that are not reachable from the
+/// production dependencies of the root and workspace manifests
+fn retrieve_lockfile_dev_packages(configurations: &Cli) -> Result<Option<Vec<PathBuf>>, Error> {
+    let project_root = &configurations.project_root_location;
+    let node_modules = &configurations.node_modules_location;
+
+    let npm_lockfile = project_root.join(NPM_LOCKFILE);
+    if npm_lockfile.is_file() {
+        let lockfile: Value = serde_json::from_str(&fs::read_to_string(&npm_lockfile)?)?;
+        return Ok(Some(retrieve_npm_dev_packages(project_root, &lockfile)?));
+    }
+
+    let graph = if project_root.join(PNPM_LOCKFILE).is_file() {
+        let content = fs::read_to_string(project_root.join(PNPM_LOCKFILE))?;
+        parse_pnpm(&YamlNode::parse(&content))?
+    } else if project_root.join(YARN_LOCKFILE).is_file() {
+        let content = fs::read_to_string(project_root.join(YARN_LOCKFILE))?;
+
+        if content.contains("__metadata:") {
+            let mut graph = parse_yarn_berry(&YamlNode::parse(&content))?;
+            graph.roots = retrieve_root_descriptors(project_root, normalize_berry_range);
+            graph
+        } else {
+            let mut graph = parse_yarn_classic(&content);
+            graph.roots = retrieve_root_descriptors(project_root, str::to_string);
+            graph
+        }
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(retrieve_installed_packages(
+        node_modules,
+        &graph.retrieve_unreached_packages(),
+    )))
+}
+
+/// Retrieves the installed packages that are only reachable through `devDependencies`,
+/// according to the npm, yarn or pnpm lockfile
+pub fn retrieve_dev_garbage(configurations: &Cli) -> Vec<PathBuf> {
+    if !configurations.prune_dev_dependencies {
+        return Vec::new();
+    }
+
+    let mut garbage = match retrieve_lockfile_dev_packages(configurations) {
+        Ok(Some(garbage)) => garbage,
+        Ok(None) => {
+            println!("No lockfile found. Dev dependencies pruning skipped");
+            return Vec::new();
+        }
+        Err(error) => {
+            println!("Failed to read the lockfile: {error}. Dev dependencies pruning skipped");
+            return Vec::new();
+        }
+    };
+
+    // Packages come before their nested `node_modules`, which are removed with them
+    garbage.sort();
+    garbage.dedup_by(|path, dir| path.starts_with(dir));
+
+    let links = retrieve_garbage_links(&configurations.node_modules_location, &garbage);
+    garbage.extend(links);
+    garbage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    fn write_packages(temp: &TempDir, packages: &[(&str, &str, &str)]) {
+        for (dir, name, version) in packages {
+            temp.child(dir)
+                .child("package.json")
+                .write_str(&format!(r#"{{"name":"{name}","version":"{version}"}}"#))
+                .unwrap();
+        }
+    }
+
+    fn retrieve_garbage(temp: &TempDir) -> Vec<PathBuf> {
+        let root = temp.path().canonicalize().unwrap();
+
+        retrieve_dev_garbage(&Cli {
+            project_root_location: root.clone(),
+            node_modules_location: root.join(NODE_MODULES),
+            prune_dev_dependencies: true,
+            ..Default::default()
+        })
+        .into_iter()
+        .map(|path| path.strip_prefix(&root).unwrap().to_path_buf())
+        .collect()
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let lockfile = YamlNode::parse(
+            r#"# comment
+lockfileVersion: '6.0'
+
+packages:
+
+  /@babel/core@7.23.0:
+    resolution: {integrity: sha512-a, tarball: "https://example.com/a.tgz"}
+    cpu:
+      - x64
+    description: |
+      multi-line: scalar
+    dependencies:
+      '@babel/code-frame': 7.22.13
+      "debug@npm:^4.0.0": "npm:4.3.4"
+"#,
+        );
+        let package = lockfile
+            .get("packages")
+            .and_then(|packages| packages.get("/@babel/core@7.23.0"))
+            .unwrap();
+        let dependencies: Vec<(&str, Option<&str>)> = package
+            .get("dependencies")
+            .unwrap()
+            .entries
+            .iter()
+            .map(|(name, version)| (name.as_str(), version.as_str()))
+            .collect();
+
+        assert_eq!(
+            lockfile.get("lockfileVersion").and_then(YamlNode::as_str),
+            Some("6.0")
+        );
+        assert_eq!(
+            package.get("resolution").and_then(YamlNode::as_str),
+            Some(r#"{integrity: sha512-a, tarball: "https://example.com/a.tgz"}"#)
+        );
+        assert!(package.get("cpu").unwrap().entries.is_empty());
+        assert!(package.get("multi-line").is_none());
+        assert_eq!(
+            dependencies,
+            vec![
+                ("@babel/code-frame", Some("7.22.13")),
+                ("debug@npm:^4.0.0", Some("npm:4.3.4"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_npm_dev_garbage() {
+        let temp = TempDir::new().unwrap();
+
+        temp.child(NPM_LOCKFILE)
+            .write_str(
+                r#"{
+                    "lockfileVersion": 3,
+                    "packages": {
+                        "": {"dependencies": {"express": "^4.0.0"}, "devDependencies": {"jest": "^29.0.0"}},
+                        "node_modules/express": {"version": "4.18.2"},
+                        "node_modules/jest": {"version": "29.7.0", "dev": true},
+                        "node_modules/jest/node_modules/ms": {"version": "2.0.0", "dev": true},
+                        "node_modules/fsevents": {"version": "2.3.3", "devOptional": true}
+                    }
+                }"#,
+            )
+            .unwrap();
+        write_packages(
+            &temp,
+            &[
+                ("node_modules/express", "express", "4.18.2"),
+                ("node_modules/jest", "jest", "29.7.0"),
+                ("node_modules/jest/node_modules/ms", "ms", "2.0.0"),
+                ("node_modules/fsevents", "fsevents", "2.3.3"),
+            ],
+        );
+
+        assert_eq!(
+            retrieve_garbage(&temp),
+            vec![PathBuf::from("node_modules/jest")]
+        );
+    }
+
+    #[test]
+    fn test_yarn_dev_garbage() {
+        let temp = TempDir::new().unwrap();
+
+        temp.child("package.json")
+            .write_str(
+                r#"{"dependencies":{"debug":"^4.0.0"},"devDependencies":{"mocha":"^10.0.0"}}"#,
+            )
+            .unwrap();
+        temp.child(YARN_LOCKFILE)
+            .write_str(
+                r#"# yarn lockfile v1
+
+
+debug@^4.0.0, debug@^4.3.4:
+  version "4.3.4"
+  dependencies:
+    ms "2.1.2"
+
+mocha@^10.0.0:
+  version "10.2.0"
+  dependencies:
+    debug "^4.3.4"
+    ms "2.1.3"
+
+ms@2.1.2:
+  version "2.1.2"
+
+ms@2.1.3:
+  version "2.1.3"
+"#,
+            )
+            .unwrap();
+        write_packages(
+            &temp,
+            &[
+                ("node_modules/debug", "debug", "4.3.4"),
+                ("node_modules/ms", "ms", "2.1.2"),
+                ("node_modules/mocha", "mocha", "10.2.0"),
+                ("node_modules/mocha/node_modules/ms", "ms", "2.1.3"),
+            ],
+        );
+
+        assert_eq!(
+            retrieve_garbage(&temp),
+            vec![PathBuf::from("node_modules/mocha")]
+        );
+
+        temp.child(YARN_LOCKFILE)
+            .write_str(
+                r#"__metadata:
+  version: 8
+
+"debug@npm:^4.0.0, debug@npm:^4.3.4":
+  version: 4.3.4
+  resolution: "debug@npm:4.3.4"
+  dependencies:
+    ms: "npm:2.1.2"
+
+"mocha@npm:^10.0.0":
+  version: 10.2.0
+  resolution: "mocha@npm:10.2.0"
+  dependencies:
+    debug: "npm:^4.3.4"
+    ms: "npm:2.1.3"
+
+"ms@npm:2.1.2":
+  version: 2.1.2
+  resolution: "ms@npm:2.1.2"
+
+"ms@npm:2.1.3":
+  version: 2.1.3
+  resolution: "ms@npm:2.1.3"
+
+"root@workspace:.":
+  version: 0.0.0-use.local
+  resolution: "root@workspace:."
+  dependencies:
+    debug: "npm:^4.0.0"
+    mocha: "npm:^10.0.0"
+"#,
+            )
+            .unwrap();
+
+        assert_eq!(
+            retrieve_garbage(&temp),
+            vec![PathBuf::from("node_modules/mocha")]
+        );
+    }
+
+    #[test]
+    fn test_pnpm_dev_garbage() {
+        let temp = TempDir::new().unwrap();
+
+        temp.child(PNPM_LOCKFILE)
+            .write_str(
+                r#"lockfileVersion: '9.0'
+
+importers:
+  .:
+    dependencies:
+      react-dom:
+        specifier: ^18.2.0
+        version: 18.2.0(react@18.2.0)
+    devDependencies:
+      vitest:
+        specifier: ^1.0.0
+        version: 1.6.0
+
+packages:
+  react-dom@18.2.0:
+    resolution: {integrity: sha512-a}
+  react@18.2.0:
+    resolution: {integrity: sha512-b}
+  vitest@1.6.0:
+    resolution: {integrity: sha512-c}
+
+snapshots:
+  react-dom@18.2.0(react@18.2.0):
+    dependencies:
+      react: 18.2.0
+  react@18.2.0: {}
+  vitest@1.6.0:
+    dependencies:
+      react: 18.2.0
+"#,
+            )
+            .unwrap();
+        write_packages(
+            &temp,
+            &[
+                (
+                    "node_modules/.pnpm/react-dom@18.2.0_react@18.2.0/node_modules/react-dom",
+                    "react-dom",
+                    "18.2.0",
+                ),
+                (
+                    "node_modules/.pnpm/react@18.2.0/node_modules/react",
+                    "react",
+                    "18.2.0",
+                ),
+                (
+                    "node_modules/.pnpm/vitest@1.6.0/node_modules/vitest",
+                    "vitest",
+                    "1.6.0",
+                ),
+            ],
+        );
+        std::os::unix::fs::symlink(
+            temp.path()
+                .join("node_modules/.pnpm/vitest@1.6.0/node_modules/vitest"),
+            temp.path().join("node_modules/vitest"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            retrieve_garbage(&temp),
+            vec![
+                PathBuf::from("node_modules/.pnpm/vitest@1.6.0/node_modules/vitest"),
+                PathBuf::from("node_modules/vitest"),
+            ]
+        );
+    }
+}
@@ -1,6 +1,6 @@
 //! Minify JavaScript files
 
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use oxc_allocator::Allocator;
 use oxc_codegen::{Codegen, CodegenOptions};
 use oxc_minifier::{Minifier, MinifierOptions};
@@ -76,24 +76,120 @@ pub fn minify_js(configurations: &Cli) {
     }
 }
 
+/// Whether the character can be part of a number or of a literal like `true`
+fn is_scalar_character(character: char) -> bool {
+    character.is_ascii_alphanumeric() || matches!(character, '.' | '-' | '+')
+}
+
+/// Minify a JSON text, keeping the order of the keys
+///
+/// Whitespace, comments and trailing commas outside of strings are stripped, so JSONC files
+/// (like `tsconfig.json`) are supported. The result must parse back to the very same text, so
+/// texts that `serde_json` would alter (like duplicate keys or escaped characters) are rejected.
+fn minify_json_text(source: &str) -> Result<String, Error> {
+    let mut minified = String::with_capacity(source.len());
+    let mut characters = source.trim_start_matches('\u{feff}').chars().peekable();
+    let mut has_pending_comma = false;
+    let mut has_separator = false;
+
+    while let Some(character) = characters.next() {
+        match character {
+            '"' => {
+                has_separator = false;
+                if has_pending_comma {
+                    minified.push(',');
+                    has_pending_comma = false;
+                }
+
+                minified.push(character);
+                loop {
+                    let character = characters
+                        .next()
+                        .ok_or_else(|| anyhow!("unterminated string"))?;
+                    minified.push(character);
+
+                    match character {
+                        '\\' => minified.extend(characters.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if characters.peek() == Some(&'/') => {
+                has_separator = true;
+                while characters.next_if(|character| *character != '\n').is_some() {}
+            }
+            '/' if characters.peek() == Some(&'*') => {
+                has_separator = true;
+                characters.next();
+                let mut previous = ' ';
+                loop {
+                    let character = characters
+                        .next()
+                        .ok_or_else(|| anyhow!("unterminated comment"))?;
+                    if previous == '*' && character == '/' {
+                        break;
+                    }
+                    previous = character;
+                }
+            }
+            ',' => {
+                has_pending_comma = true;
+                has_separator = false;
+            }
+            '}' | ']' => {
+                has_pending_comma = false;
+                has_separator = false;
+                minified.push(character);
+            }
+            _ if character.is_whitespace() => has_separator = true,
+            _ => {
+                // Separated tokens like `1 2` must not be merged into a single `12` token
+                if has_separator
+                    && !has_pending_comma
+                    && is_scalar_character(character)
+                    && minified.ends_with(is_scalar_character)
+                {
+                    return Err(anyhow!("missing separator between two values"));
+                }
+
+                has_separator = false;
+                if has_pending_comma {
+                    minified.push(',');
+                    has_pending_comma = false;
+                }
+                minified.push(character);
+            }
+        }
+    }
+
+    let round_trip = serde_json::to_string(&serde_json::from_str::<serde_json::Value>(&minified)?)?;
+
+    if round_trip == minified {
+        Ok(minified)
+    } else {
+        Err(anyhow!("the minified JSON does not round-trip exactly"))
+    }
+}
+
+/// Minify JSON files
+///
+/// This function minifies the JSON and JSONC files of the `node_modules` directory, keeping the
+/// order of their keys.
 pub fn minify_json(configurations: &Cli) {
     let to_minify = retrieve_files_by_extension(configurations, "json");
 
     for path in to_minify {
-        match fs::read_to_string(&path) {
-            Ok(json_string) => match serde_json::from_str::<serde_json::Value>(&json_string) {
-                Ok(json_reader) => match serde_json::to_string(&json_reader) {
-                    Ok(minified_json_string) => match fs::write(&path, minified_json_string) {
-                        Ok(()) => println!("File minified: {}", path.display()),
-                        Err(error) => {
-                            println!("Failed to write file {}: {}", path.display(), error);
-                        }
-                    },
-                    Err(error) => println!("Failed to minify file {}: {}", path.display(), error),
-                },
-                Err(error) => println!("Failed to parse file {}: {}", path.display(), error),
+        let minified = fs::read_to_string(&path)
+            .map_err(Error::from)
+            .and_then(|json| minify_json_text(&json));
+
+        match minified {
+            Ok(json) => match fs::write(&path, json) {
+                Ok(()) => println!("File minified: {}", path.display()),
+                Err(error) => println!("Failed to write file {}: {}", path.display(), error),
             },
-            Err(error) => println!("Failed to read file {}: {}", path.display(), error),
+            Err(error) => println!("Failed to minify file {}: {}", path.display(), error),
         }
     }
 }
@@ -140,4 +236,52 @@ mod tests_compile {
             "(function(){let e=require(`path`);require.resolve(`stream`),require(`depd`)(`body-parser`),e.join(require(`module`))})(),module.exports=function(e){return path.extname(e)===`.md`};"
         );
     }
+
+    #[test]
+    fn test_minify_json_order() {
+        let json = r#"{
+            "name": "trees",
+            "exports": {
+                "./feature": { "require": "./feature.cjs", "import": "./feature.mjs" },
+                ".": { "node": "./node.js", "default": "./index.js" }
+            }
+        }"#;
+
+        assert_eq!(
+            minify_json_text(json).unwrap(),
+            r#"{"name":"trees","exports":{"./feature":{"require":"./feature.cjs","import":"./feature.mjs"},".":{"node":"./node.js","default":"./index.js"}}}"#
+        );
+    }
+
+    #[test]
+    fn test_minify_jsonc() {
+        let json = r#"{
+            // Compiler options
+            "compilerOptions": {
+                "outDir": "./dist", /* build output */
+                "paths": { "@/*": ["src/*", ], },
+            },
+            "include": ["src/**/*", "https://example.com/a,b"],
+        }"#;
+
+        assert_eq!(
+            minify_json_text(json).unwrap(),
+            r#"{"compilerOptions":{"outDir":"./dist","paths":{"@/*":["src/*"]}},"include":["src/**/*","https://example.com/a,b"]}"#
+        );
+    }
+
+    #[test]
+    fn test_minify_json_round_trip() {
+        assert!(minify_json_text(r#"{"a": 1, "a": 2}"#).is_err());
+        assert!(minify_json_text(r#"{"a": "\u00e9"}"#).is_err());
+        assert!(minify_json_text(r#"{"a": /* unterminated"#).is_err());
+        assert!(minify_json_text("[1 2]").is_err());
+        assert!(minify_json_text("[1/* comment */2]").is_err());
+        assert!(minify_json_text("[tr\nue]").is_err());
+        assert_eq!(minify_json_text("[1 , 2 ]").unwrap(), "[1,2]");
+        assert_eq!(
+            minify_json_text(r#"{"a": "quote \" // not a comment"}"#).unwrap(),
+            r#"{"a":"quote \" // not a comment"}"#
+        );
+    }
 }
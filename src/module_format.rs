@@ -0,0 +1,319 @@
+//! Module format pruning of dual CJS/ESM packages
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    configurations::{Cli, ModuleFormat},
+    module_graph::Visitor,
+    package::{
+        collect_condition_targets, package_root, read_manifest, resolve_target, retrieve_manifests,
+    },
+};
+
+const MANIFEST: &str = "package.json";
+
+/// Resolves the CJS or the ESM targets of a package
+///
+/// CJS targets come from the `require` condition and the `main` field, ESM targets from the
+/// `import` condition and the `module` field.
+fn resolve_format_targets(package_root: &Path, manifest: &Value, is_cjs: bool) -> Vec<PathBuf> {
+    let (condition, field) = if is_cjs {
+        ("require", "main")
+    } else {
+        ("import", "module")
+    };
+
+    collect_condition_targets(&manifest["exports"], condition)
+        .into_iter()
+        .chain(manifest[field].as_str().map(str::to_owned))
+        .flat_map(|target| resolve_target(package_root, &target))
+        .filter_map(|path| path.canonicalize().ok())
+        .collect()
+}
+
+/// Resolves the targets of the kept and of the dropped format of a dual package
+///
+/// Returns `None` when the package does not ship both formats.
+fn resolve_dual_targets(
+    package_root: &Path,
+    manifest: &Value,
+    keep_cjs: bool,
+) -> Option<(Vec<PathBuf>, Vec<PathBuf>)> {
+    // Node.js only reads `module` through bundlers, so it loads `main` without an `import` condition
+    if !keep_cjs && collect_condition_targets(&manifest["exports"], "import").is_empty() {
+        return None;
+    }
+
+    let kept_targets = resolve_format_targets(package_root, manifest, keep_cjs);
+    let dropped_targets: Vec<PathBuf> = resolve_format_targets(package_root, manifest, !keep_cjs)
+        .into_iter()
+        .filter(|target| !kept_targets.contains(target))
+        .collect();
+
+    (!kept_targets.is_empty() && !dropped_targets.is_empty())
+        .then_some((kept_targets, dropped_targets))
+}
+
+/// Retrieves the unused CJS or ESM builds of every dual package
+///
+/// The files reached from the targets of the dropped format are removed, unless the targets of
+/// the kept format reach them too (like an ESM wrapper that imports the CJS build). Manifests are
+/// never removed.
+///
+/// With [`ModuleFormat::Auto`] the format of each package comes from the module graph, so only
+/// packages loaded in a single format are pruned. Paths of the module graph are never removed.
+pub fn retrieve_format_garbage(
+    configurations: &Cli,
+    package_formats: &HashMap<PathBuf, bool>,
+    module_graph: &HashSet<PathBuf>,
+) -> Vec<PathBuf> {
+    let Some(keep_format) = &configurations.keep_format else {
+        return Vec::new();
+    };
+
+    let mut pruned_packages: HashSet<PathBuf> = HashSet::new();
+    let mut kept_targets: Vec<PathBuf> = Vec::new();
+    let mut dropped_targets: Vec<PathBuf> = Vec::new();
+
+    for manifest_path in retrieve_manifests(&configurations.node_modules_location) {
+        let keep_cjs = match keep_format {
+            ModuleFormat::Cjs => Some(true),
+            ModuleFormat::Esm => Some(false),
+            ModuleFormat::Auto => package_formats.get(&manifest_path).copied(),
+        };
+
+        if let (Some(keep_cjs), Some(package_root), Some(manifest)) = (
+            keep_cjs,
+            manifest_path.parent(),
+            read_manifest(&manifest_path),
+        ) {
+            if let Some((kept, dropped)) = resolve_dual_targets(package_root, &manifest, keep_cjs) {
+                pruned_packages.insert(package_root.to_path_buf());
+                kept_targets.extend(kept);
+                dropped_targets.extend(dropped);
+            }
+        }
+    }
+
+    if pruned_packages.is_empty() {
+        return Vec::new();
+    }
+
+    let mut kept_visitor = Visitor::from_files(kept_targets, true);
+    let kept_paths = kept_visitor.run();
+
+    let mut dropped_visitor = Visitor::from_files(dropped_targets, false);
+    let dropped_paths = dropped_visitor.run();
+
+    // The files that dynamic code loads are unknown, so these packages are left untouched
+    for package in kept_visitor
+        .hazardous_packages()
+        .iter()
+        .chain(&dropped_visitor.hazardous_packages())
+    {
+        pruned_packages.remove(package);
+    }
+
+    dropped_paths
+        .into_iter()
+        .filter(|path| !kept_paths.contains(path) && !module_graph.contains(path))
+        .filter(|path| path.file_name() != Some(MANIFEST.as_ref()))
+        .filter(|path| package_root(path).is_some_and(|root| pruned_packages.contains(&root)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    fn create_dual_packages() -> (TempDir, PathBuf) {
+        let temp = TempDir::new().unwrap();
+
+        temp.child("node_modules/trees/package.json")
+            .write_str(
+                r#"{
+                    "name": "trees",
+                    "main": "./dist/cjs/index.js",
+                    "module": "./dist/esm/index.js",
+                    "exports": {
+                        ".": {
+                            "import": "./dist/esm/index.js",
+                            "require": "./dist/cjs/index.js"
+                        },
+                        "./package.json": "./package.json"
+                    }
+                }"#,
+            )
+            .unwrap();
+        temp.child("node_modules/trees/dist/cjs/index.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/trees/dist/esm/index.js")
+            .write_str("import './utils/helper.js';")
+            .unwrap();
+        temp.child("node_modules/trees/dist/esm/utils/helper.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/twins/package.json")
+            .write_str(
+                r#"{
+                    "name": "twins",
+                    "exports": { "import": "./index.mjs", "require": "./index.cjs" }
+                }"#,
+            )
+            .unwrap();
+        temp.child("node_modules/twins/index.mjs").touch().unwrap();
+        temp.child("node_modules/twins/index.cjs").touch().unwrap();
+        temp.child("node_modules/legacy/package.json")
+            .write_str(r#"{"name":"legacy","main":"lib/index.js","module":"es/index.js"}"#)
+            .unwrap();
+        temp.child("node_modules/legacy/lib/index.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/legacy/es/index.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/ws/package.json")
+            .write_str(
+                r#"{
+                    "name": "ws",
+                    "main": "index.js",
+                    "exports": {
+                        ".": { "import": "./wrapper.mjs", "require": "./index.js" }
+                    }
+                }"#,
+            )
+            .unwrap();
+        temp.child("node_modules/ws/wrapper.mjs")
+            .write_str("import WebSocket from './index.js';\nexport default WebSocket;")
+            .unwrap();
+        temp.child("node_modules/ws/index.js")
+            .write_str("module.exports = require('./lib/websocket');")
+            .unwrap();
+        temp.child("node_modules/ws/lib/websocket.js")
+            .touch()
+            .unwrap();
+
+        let path = temp.path().canonicalize().unwrap();
+
+        (temp, path)
+    }
+
+    fn retrieve_sorted_garbage(
+        configurations: &Cli,
+        package_formats: &HashMap<PathBuf, bool>,
+        module_graph: &HashSet<PathBuf>,
+    ) -> Vec<PathBuf> {
+        let mut garbage = retrieve_format_garbage(configurations, package_formats, module_graph);
+        garbage.sort();
+        garbage
+    }
+
+    #[test]
+    fn test_keep_cjs() {
+        let (_temp, path) = create_dual_packages();
+        let node_modules = path.join("node_modules");
+
+        let configurations = Cli {
+            node_modules_location: node_modules.clone(),
+            keep_format: Some(ModuleFormat::Cjs),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            retrieve_sorted_garbage(&configurations, &HashMap::new(), &HashSet::new()),
+            vec![
+                node_modules.join("legacy/es/index.js"),
+                node_modules.join("trees/dist/esm/index.js"),
+                node_modules.join("trees/dist/esm/utils/helper.js"),
+                node_modules.join("twins/index.mjs"),
+                node_modules.join("ws/wrapper.mjs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keep_hazardous() {
+        let temp = TempDir::new().unwrap();
+        let node_modules = temp.path().canonicalize().unwrap().join("node_modules");
+
+        temp.child("node_modules/dual/package.json")
+            .write_str(
+                r#"{
+                    "name": "dual",
+                    "exports": { "import": "./esm/index.mjs", "require": "./cjs/index.js" }
+                }"#,
+            )
+            .unwrap();
+        temp.child("node_modules/dual/esm/index.mjs")
+            .write_str("export default eval('1 + 1');")
+            .unwrap();
+        temp.child("node_modules/dual/cjs/index.js")
+            .write_str("module.exports = require('../data/table.json');")
+            .unwrap();
+        temp.child("node_modules/dual/data/table.json")
+            .write_str("{}")
+            .unwrap();
+        temp.child("node_modules/dual/bin/cli.js").touch().unwrap();
+
+        let configurations = Cli {
+            node_modules_location: node_modules,
+            keep_format: Some(ModuleFormat::Cjs),
+            ..Default::default()
+        };
+
+        assert!(
+            retrieve_sorted_garbage(&configurations, &HashMap::new(), &HashSet::new()).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_keep_esm() {
+        let (_temp, path) = create_dual_packages();
+        let node_modules = path.join("node_modules");
+
+        let configurations = Cli {
+            node_modules_location: node_modules.clone(),
+            keep_format: Some(ModuleFormat::Esm),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            retrieve_sorted_garbage(&configurations, &HashMap::new(), &HashSet::new()),
+            vec![
+                node_modules.join("trees/dist/cjs/index.js"),
+                node_modules.join("twins/index.cjs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keep_auto() {
+        let (_temp, path) = create_dual_packages();
+        let node_modules = path.join("node_modules");
+
+        let configurations = Cli {
+            node_modules_location: node_modules.clone(),
+            keep_format: Some(ModuleFormat::Auto),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            retrieve_sorted_garbage(
+                &configurations,
+                &HashMap::from([
+                    (node_modules.join("trees/package.json"), false),
+                    (node_modules.join("twins/package.json"), true),
+                ]),
+                &HashSet::from([node_modules.join("twins/index.mjs")]),
+            ),
+            vec![node_modules.join("trees/dist/cjs/index.js")]
+        );
+    }
+}
@@ -1,22 +1,128 @@
 #![allow(clippy::print_stdout)]
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
 };
 
 use oxc_allocator::Allocator;
-use oxc_ast::{ast::Expression};
-use oxc_ast_visit::Visit;
+use oxc_ast::ast::{Argument, Expression};
+use oxc_ast_visit::{walk, Visit};
 use oxc_parser::{ParseOptions, Parser};
 use oxc_resolver::{ResolveOptions, Resolver};
 use oxc_span::SourceType;
 
-use crate::configurations::Cli;
+use crate::{
+    bundle::retrieve_bundle_outputs,
+    configurations::Cli,
+    glob::retrieve_glob_paths,
+    package::{
+        collect_targets, is_in_nested_node_modules, package_root, read_manifest, resolve_target,
+    },
+    presets::Preset,
+};
+
+/// List of Node.js builtin modules that can be required without the `node:` prefix
+static NODE_BUILTIN_MODULES: &[&str] = &[
+    "_http_agent",
+    "_http_client",
+    "_http_common",
+    "_http_incoming",
+    "_http_outgoing",
+    "_http_server",
+    "_stream_duplex",
+    "_stream_passthrough",
+    "_stream_readable",
+    "_stream_transform",
+    "_stream_wrap",
+    "_stream_writable",
+    "_tls_common",
+    "_tls_wrap",
+    "assert",
+    "assert/strict",
+    "async_hooks",
+    "buffer",
+    "child_process",
+    "cluster",
+    "console",
+    "constants",
+    "crypto",
+    "dgram",
+    "diagnostics_channel",
+    "dns",
+    "dns/promises",
+    "domain",
+    "events",
+    "fs",
+    "fs/promises",
+    "http",
+    "http2",
+    "https",
+    "inspector",
+    "inspector/promises",
+    "module",
+    "net",
+    "os",
+    "path",
+    "path/posix",
+    "path/win32",
+    "perf_hooks",
+    "process",
+    "punycode",
+    "querystring",
+    "readline",
+    "readline/promises",
+    "repl",
+    "stream",
+    "stream/consumers",
+    "stream/promises",
+    "stream/web",
+    "string_decoder",
+    "sys",
+    "timers",
+    "timers/promises",
+    "tls",
+    "trace_events",
+    "tty",
+    "url",
+    "util",
+    "util/types",
+    "v8",
+    "vm",
+    "wasi",
+    "worker_threads",
+    "zlib",
+];
 
 #[derive(Debug, PartialEq, Eq, Hash)]
 struct ModuleToVisit {
     name: String,
     is_cjs: bool,
+    is_optional: bool,
+}
+
+/// Reason why a file cannot be analyzed statically
+#[derive(strum::Display, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HazardReason {
+    #[strum(to_string = "eval")]
+    Eval,
+    #[strum(to_string = "new Function")]
+    FunctionConstructor,
+    #[strum(to_string = "vm.runIn*Context")]
+    VmRunInContext,
+    #[strum(to_string = "require with a non-literal argument")]
+    DynamicRequire,
+    #[strum(to_string = "Module._load")]
+    ModuleLoad,
+}
+
+/// A dynamic code site found while visiting a file
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hazard {
+    /// Root of the package that contains the file, or the file itself outside of `node_modules`
+    pub package: PathBuf,
+    /// File that contains the dynamic code
+    pub path: PathBuf,
+    pub reason: HazardReason,
 }
 
 pub struct Visitor {
@@ -24,21 +130,68 @@ pub struct Visitor {
     files_to_visit: VecDeque<PathBuf>,
     paths_found: HashSet<PathBuf>,
     current_path: PathBuf,
+    optional_depth: usize,
+    drop_optional: bool,
+    hazards: HashSet<Hazard>,
+    hazardous_packages: HashSet<PathBuf>,
+    keep_hazardous: bool,
+    external_modules: Vec<(PathBuf, String, bool)>,
+    keep_exports: bool,
+    visited_manifests: HashSet<PathBuf>,
+    loaded_formats: HashMap<PathBuf, HashSet<bool>>,
 }
 
 impl<'a> Visitor {
-    pub fn new(configurations: &Cli) -> Self {
+    pub fn new(configurations: &Cli, applied_presets: &[(PathBuf, Preset)]) -> Self {
+        let bundle_outputs = retrieve_bundle_outputs(configurations);
+
         let initial_files = [
-            configurations.keep_files(),
+            configurations.keep_files(applied_presets),
             configurations.entry_point_location.clone(),
+            bundle_outputs
+                .iter()
+                .filter_map(|output| output.path.canonicalize().ok())
+                .collect(),
         ]
         .concat();
 
+        let external_modules = bundle_outputs
+            .into_iter()
+            .flat_map(|output| {
+                output
+                    .externals
+                    .into_iter()
+                    .map(move |(module, is_cjs)| (output.path.clone(), module, is_cjs))
+            })
+            .collect();
+
+        Self {
+            drop_optional: configurations.drop_optional,
+            external_modules,
+            keep_exports: configurations.keep_exports,
+            ..Self::from_files(initial_files, configurations.keep_hazardous)
+        }
+    }
+
+    /// Creates a visitor that only starts from the given files
+    ///
+    /// With `keep_hazardous`, packages with dynamic code are visited whole, so that none of their
+    /// files is missed.
+    pub fn from_files(initial_files: Vec<PathBuf>, keep_hazardous: bool) -> Self {
         Self {
             modules_to_visit: HashSet::new(),
             files_to_visit: VecDeque::from(initial_files.clone()),
             paths_found: initial_files.into_iter().collect::<HashSet<PathBuf>>(),
             current_path: PathBuf::new(),
+            optional_depth: 0,
+            drop_optional: false,
+            hazards: HashSet::new(),
+            hazardous_packages: HashSet::new(),
+            keep_hazardous,
+            external_modules: Vec::new(),
+            keep_exports: false,
+            visited_manifests: HashSet::new(),
+            loaded_formats: HashMap::new(),
         }
     }
 
@@ -89,17 +242,38 @@ impl<'a> Visitor {
         module.starts_with("..") || module.starts_with('.')
     }
 
+    /// Whether the specifier refers to a Node.js builtin module.
+    ///
+    /// The `node:` prefix always wins, even for modules that only exist with it (like `node:test`).
+    /// Without the prefix only the exact builtin names match, so `punycode/` or `buffer/` still
+    /// load the npm packages that shadow the builtins.
+    fn is_builtin_module(module: &str) -> bool {
+        module.starts_with("node:") || NODE_BUILTIN_MODULES.contains(&module)
+    }
+
     fn insert_module(&mut self, module: String, is_cjs: bool) -> bool {
+        let is_optional = self.optional_depth > 0;
+
+        if is_optional && self.drop_optional && !Self::is_local_module(&module) {
+            println!(
+                "Dropping optional module {} required by {}",
+                module,
+                self.current_path.display()
+            );
+            return false;
+        }
+
         self.modules_to_visit.insert(ModuleToVisit {
             name: module,
             is_cjs,
+            is_optional,
         })
     }
 
     fn is_file_module(module: &str) -> bool {
-        Path::new(&module).extension().is_some_and(|ext| {
-            ext.eq_ignore_ascii_case("json") || ext.eq_ignore_ascii_case("node")
-        })
+        Path::new(&module)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json") || ext.eq_ignore_ascii_case("node"))
     }
 
     fn insert_module_to_visit(&mut self, module: String, is_cjs: bool) {
@@ -116,7 +290,7 @@ impl<'a> Visitor {
                     self.insert_module(module, is_cjs);
                 }
             }
-        } else if !module.starts_with("node:") {
+        } else if !Self::is_builtin_module(&module) {
             self.insert_module(module, is_cjs);
         }
     }
@@ -127,16 +301,170 @@ impl<'a> Visitor {
         }
     }
 
+    /// Inserts the dependency array of an AMD `define(['dep'], factory)` or `require(['dep'], cb)`
+    /// call, skipping the special `require`, `exports` and `module` dependencies.
+    fn insert_amd_dependencies(&mut self, it: &oxc_ast::ast::CallExpression<'a>) {
+        let dependencies =
+            it.arguments
+                .iter()
+                .take(2)
+                .find_map(|argument| match argument.as_expression() {
+                    Some(Expression::ArrayExpression(array)) => Some(array),
+                    _ => None,
+                });
+
+        if let Some(dependencies) = dependencies {
+            for dependency in &dependencies.elements {
+                if let Some(Expression::StringLiteral(lit)) = dependency.as_expression() {
+                    if !matches!(lit.value.as_str(), "require" | "exports" | "module") {
+                        self.insert_module_to_visit(lit.value.to_string(), true);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handles the `require` calls that are not a plain CommonJS require of a string literal
+    fn insert_non_literal_require(&mut self, it: &oxc_ast::ast::CallExpression<'a>) {
+        match it.arguments.first().and_then(Argument::as_expression) {
+            None => {}
+            Some(Expression::ArrayExpression(_)) => self.insert_amd_dependencies(it),
+            Some(Expression::TemplateLiteral(template)) => match template.single_quasi() {
+                Some(module) => self.insert_module_to_visit(module.to_string(), true),
+                None => self.insert_hazard(HazardReason::DynamicRequire),
+            },
+            Some(_) => self.insert_hazard(HazardReason::DynamicRequire),
+        }
+    }
+
+    fn member_hazard(
+        it: &oxc_ast::ast::CallExpression<'a>,
+        object: &Expression<'a>,
+    ) -> Option<HazardReason> {
+        match it.callee_name() {
+            Some("runInThisContext" | "runInNewContext" | "runInContext") => {
+                Some(HazardReason::VmRunInContext)
+            }
+            Some("_load")
+                if object.is_specific_id("Module")
+                    || matches!(
+                        object.get_inner_expression(),
+                        Expression::CallExpression(call)
+                            if call.common_js_require().is_some_and(|lit| lit.value == "module")
+                    ) =>
+            {
+                Some(HazardReason::ModuleLoad)
+            }
+            _ => None,
+        }
+    }
+
+    /// Records a dynamic code site in the current file
+    ///
+    /// When hazardous packages have to be kept whole, every file of the package is queued as well.
+    fn insert_hazard(&mut self, reason: HazardReason) {
+        let package = package_root(&self.current_path);
+
+        if let Some(package) = package.as_ref() {
+            if self.keep_hazardous && self.hazardous_packages.insert(package.clone()) {
+                self.add_package_to_visit(package);
+            }
+        }
+
+        self.hazards.insert(Hazard {
+            package: package.unwrap_or_else(|| self.current_path.clone()),
+            path: self.current_path.clone(),
+            reason,
+        });
+    }
+
+    fn add_package_to_visit(&mut self, package: &Path) {
+        let package_files =
+            retrieve_glob_paths(vec![package.join("**").join("*").display().to_string()])
+                .into_iter()
+                .filter(|path| path.is_file())
+                .filter(|path| !is_in_nested_node_modules(package, path));
+
+        for path in package_files {
+            self.add_path_to_visit(path);
+        }
+    }
+
+    /// Prints the packages that contain dynamic code hazards
+    pub fn report_hazards(&self) {
+        if self.hazards.is_empty() {
+            return;
+        }
+
+        let mut hazards: Vec<&Hazard> = self.hazards.iter().collect();
+        hazards.sort();
+
+        println!("Dynamic code hazards found:");
+        for hazard in hazards {
+            println!(
+                "{}: {} in {}",
+                hazard.package.display(),
+                hazard.reason,
+                hazard.path.display()
+            );
+        }
+    }
+
+    /// Returns the packages (or files outside of `node_modules`) that contain dynamic code hazards
+    pub fn hazardous_packages(&self) -> HashSet<PathBuf> {
+        self.hazards
+            .iter()
+            .map(|hazard| hazard.package.clone())
+            .collect()
+    }
+
+    /// Returns whether each package manifest was only loaded as CJS (`true`) or as ESM (`false`)
+    ///
+    /// Packages loaded in both formats are left out.
+    pub fn package_formats(&self) -> HashMap<PathBuf, bool> {
+        self.loaded_formats
+            .iter()
+            .filter(|(_, formats)| formats.len() == 1)
+            .filter_map(|(manifest, formats)| {
+                formats
+                    .iter()
+                    .next()
+                    .map(|is_cjs| (manifest.clone(), *is_cjs))
+            })
+            .collect()
+    }
+
     fn resolve_modules_to_visit(&mut self) {
         let specifiers: Vec<ModuleToVisit> = self.modules_to_visit.drain().collect();
 
         for specifier in specifiers {
             let resolver = Self::build_resolver(specifier.is_cjs);
             match resolver.resolve(&self.current_path, &specifier.name) {
-                Err(_) => {}
+                Err(err) if specifier.is_optional => println!(
+                    "Optional module {} not found from {}: {}",
+                    specifier.name,
+                    self.current_path.display(),
+                    err
+                ),
+                Err(err) => println!(
+                    "Error while resolving {} from {}: {}",
+                    specifier.name,
+                    self.current_path.display(),
+                    err
+                ),
                 Ok(resolution) => {
                     if let Some(package_json) = resolution.package_json() {
                         self.add_path(package_json.realpath.clone());
+                        self.loaded_formats
+                            .entry(package_json.realpath.clone())
+                            .or_default()
+                            .insert(specifier.is_cjs);
+
+                        if self.keep_exports
+                            && self.visited_manifests.insert(package_json.realpath.clone())
+                        {
+                            self.add_manifest_targets_to_visit(&package_json.realpath);
+                        }
                     }
                     self.add_path_to_visit(resolution.full_path());
                 }
@@ -144,7 +472,44 @@ impl<'a> Visitor {
         }
     }
 
+    /// Queues every file referenced by the `main`, `module`, `exports` and `imports` fields of a
+    /// package, for every condition.
+    ///
+    /// Targets that map to other packages (like `"#dep": "fastify"`) are left to the resolver.
+    fn add_manifest_targets_to_visit(&mut self, manifest_path: &Path) {
+        let (Some(manifest), Some(package_root)) =
+            (read_manifest(manifest_path), manifest_path.parent())
+        else {
+            return;
+        };
+
+        let targets: Vec<PathBuf> = ["main", "module", "exports", "imports"]
+            .iter()
+            .flat_map(|field| {
+                collect_targets(&manifest[field])
+                    .into_iter()
+                    .filter(move |target| *field != "imports" || Self::is_local_module(target))
+            })
+            .flat_map(|target| resolve_target(package_root, &target))
+            .collect();
+
+        for target in targets {
+            self.add_path_to_visit(target);
+        }
+    }
+
+    /// Resolves the external modules of the bundle outputs, relative to each output
+    fn resolve_external_modules(&mut self) {
+        for (path, module, is_cjs) in std::mem::take(&mut self.external_modules) {
+            self.current_path = path;
+            self.insert_module_to_visit(module, is_cjs);
+            self.resolve_modules_to_visit();
+        }
+    }
+
     pub fn run(&mut self) -> HashSet<PathBuf> {
+        self.resolve_external_modules();
+
         while let Some(path) = self.files_to_visit.pop_front() {
             self.visit_path(path);
 
@@ -206,15 +571,59 @@ impl<'a> Visit<'a> for Visitor {
                         {
                             self.insert_first_argument(it, false);
                         }
+                    } else if it.callee_name() == Some("catch")
+                        && matches!(
+                            static_member_expression.object.get_inner_expression(),
+                            Expression::ImportExpression(_)
+                        )
+                    {
+                        self.optional_depth += 1;
+                        self.deep_call_expression(it);
+                        self.optional_depth -= 1;
                     } else {
+                        if let Some(reason) =
+                            Self::member_hazard(it, &static_member_expression.object)
+                        {
+                            self.insert_hazard(reason);
+                        }
                         self.deep_call_expression(it);
                     }
                 }
+                Expression::Identifier(identifier) => {
+                    match identifier.name.as_str() {
+                        "define" => self.insert_amd_dependencies(it),
+                        "require" => self.insert_non_literal_require(it),
+                        "eval" => self.insert_hazard(HazardReason::Eval),
+                        "Function" => self.insert_hazard(HazardReason::FunctionConstructor),
+                        _ => {}
+                    }
+                    self.deep_call_expression(it);
+                }
                 _ => self.deep_call_expression(it),
             },
         }
     }
 
+    fn visit_new_expression(&mut self, it: &oxc_ast::ast::NewExpression<'a>) {
+        if it.callee.is_specific_id("Function") {
+            self.insert_hazard(HazardReason::FunctionConstructor);
+        }
+        walk::walk_new_expression(self, it);
+    }
+
+    fn visit_try_statement(&mut self, it: &oxc_ast::ast::TryStatement<'a>) {
+        self.optional_depth += 1;
+        self.visit_block_statement(&it.block);
+        self.optional_depth -= 1;
+
+        if let Some(handler) = &it.handler {
+            self.visit_catch_clause(handler);
+        }
+        if let Some(finalizer) = &it.finalizer {
+            self.visit_block_statement(finalizer);
+        }
+    }
+
     fn visit_export_named_declaration(&mut self, it: &oxc_ast::ast::ExportNamedDeclaration<'a>) {
         if let Some(source) = it.source.as_ref() {
             self.insert_module_to_visit(source.to_string(), false);
@@ -250,99 +659,208 @@ mod specifier_tests {
             .join("ilteoood")
             .join("legit.esm.js");
 
-        let mut visitor = Visitor::new(&Cli {
-            entry_point_location: vec![path.clone()],
-            ..Default::default()
-        });
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.clone()],
+                ..Default::default()
+            },
+            &[],
+        );
+        visitor.visit_path(path);
+
+        assert_eq!(visitor.modules_to_visit, HashSet::new());
+    }
+
+    #[test]
+    fn test_esm_export() {
+        let path = retrieve_tests_dir()
+            .join("node_modules")
+            .join("ilteoood")
+            .join("unlegit.min.js");
+
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.clone()],
+                ..Default::default()
+            },
+            &[],
+        );
+        visitor.visit_path(path);
+
+        assert_eq!(
+            visitor.modules_to_visit,
+            HashSet::from([ModuleToVisit {
+                name: "fastify".to_owned(),
+                is_cjs: false,
+                is_optional: false
+            },])
+        );
+    }
+
+    #[test]
+    fn test_cjs_specifier() {
+        let path = retrieve_tests_dir()
+            .join("node_modules")
+            .join("ilteoood")
+            .join("legit.js");
+
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.clone()],
+                ..Default::default()
+            },
+            &[],
+        );
+        visitor.visit_path(path);
+
+        assert_eq!(
+            visitor.modules_to_visit,
+            HashSet::from([ModuleToVisit {
+                name: "depd".to_owned(),
+                is_cjs: true,
+                is_optional: false
+            },])
+        );
+    }
+
+    #[test]
+    fn test_amd_specifier() {
+        let path = retrieve_tests_dir()
+            .join("node_modules")
+            .join("ilteoood")
+            .join("legit.amd.js");
+
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.clone()],
+                ..Default::default()
+            },
+            &[],
+        );
         visitor.visit_path(path);
 
         assert_eq!(
             visitor.modules_to_visit,
             HashSet::from([
                 ModuleToVisit {
-                    name: "path".to_owned(),
-                    is_cjs: false
+                    name: "depd".to_owned(),
+                    is_cjs: true,
+                    is_optional: false
                 },
                 ModuleToVisit {
-                    name: "stream".to_owned(),
-                    is_cjs: false
+                    name: "busboy".to_owned(),
+                    is_cjs: true,
+                    is_optional: false
                 },
                 ModuleToVisit {
-                    name: "fs".to_owned(),
-                    is_cjs: false
+                    name: "fastify".to_owned(),
+                    is_cjs: true,
+                    is_optional: false
                 },
             ])
         );
+        assert!(visitor.paths_found.contains(
+            &retrieve_tests_dir()
+                .join("node_modules")
+                .join("ilteoood")
+                .join("legit.js")
+        ));
     }
 
     #[test]
-    fn test_esm_export() {
+    fn test_optional_specifier() {
         let path = retrieve_tests_dir()
             .join("node_modules")
             .join("ilteoood")
-            .join("unlegit.min.js");
+            .join("optional.js");
 
-        let mut visitor = Visitor::new(&Cli {
-            entry_point_location: vec![path.clone()],
-            ..Default::default()
-        });
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.clone()],
+                ..Default::default()
+            },
+            &[],
+        );
         visitor.visit_path(path);
 
         assert_eq!(
             visitor.modules_to_visit,
             HashSet::from([
+                ModuleToVisit {
+                    name: "pino-pretty".to_owned(),
+                    is_cjs: true,
+                    is_optional: true
+                },
                 ModuleToVisit {
                     name: "fastify".to_owned(),
-                    is_cjs: false
+                    is_cjs: false,
+                    is_optional: true
                 },
                 ModuleToVisit {
-                    name: "stream".to_owned(),
-                    is_cjs: false
+                    name: "busboy".to_owned(),
+                    is_cjs: true,
+                    is_optional: false
+                },
+                ModuleToVisit {
+                    name: "depd".to_owned(),
+                    is_cjs: true,
+                    is_optional: false
                 },
             ])
         );
     }
 
     #[test]
-    fn test_cjs_specifier() {
+    fn test_drop_optional_specifier() {
         let path = retrieve_tests_dir()
             .join("node_modules")
             .join("ilteoood")
-            .join("legit.js");
+            .join("optional.js");
 
-        let mut visitor = Visitor::new(&Cli {
-            entry_point_location: vec![path.clone()],
-            ..Default::default()
-        });
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.clone()],
+                drop_optional: true,
+                ..Default::default()
+            },
+            &[],
+        );
         visitor.visit_path(path);
 
         assert_eq!(
             visitor.modules_to_visit,
             HashSet::from([
                 ModuleToVisit {
-                    name: "path".to_owned(),
-                    is_cjs: true
-                },
-                ModuleToVisit {
-                    name: "stream".to_owned(),
-                    is_cjs: true
-                },
-                ModuleToVisit {
-                    name: "module".to_owned(),
-                    is_cjs: true
+                    name: "busboy".to_owned(),
+                    is_cjs: true,
+                    is_optional: false
                 },
                 ModuleToVisit {
                     name: "depd".to_owned(),
-                    is_cjs: true
+                    is_cjs: true,
+                    is_optional: false
                 },
             ])
         );
     }
+
+    #[test]
+    fn test_builtin_modules() {
+        assert!(Visitor::is_builtin_module("fs"));
+        assert!(Visitor::is_builtin_module("fs/promises"));
+        assert!(Visitor::is_builtin_module("node:fs"));
+        assert!(Visitor::is_builtin_module("node:test"));
+        assert!(!Visitor::is_builtin_module("test"));
+        assert!(!Visitor::is_builtin_module("punycode/"));
+        assert!(!Visitor::is_builtin_module("string_decoder/"));
+        assert!(!Visitor::is_builtin_module("buffer/index.js"));
+    }
 }
 
 #[cfg(test)]
 mod resolve_tests {
     use super::*;
+    use assert_fs::{prelude::*, TempDir};
     use std::env;
 
     fn retrieve_tests_dir() -> PathBuf {
@@ -355,10 +873,13 @@ mod resolve_tests {
         let tests_dir = retrieve_tests_dir();
         let path = tests_dir.join("index.js");
 
-        let mut visitor = Visitor::new(&Cli {
-            entry_point_location: vec![path.clone()],
-            ..Default::default()
-        });
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.clone()],
+                ..Default::default()
+            },
+            &[],
+        );
 
         let result = visitor.run();
 
@@ -373,4 +894,273 @@ mod resolve_tests {
             ])
         );
     }
+
+    #[test]
+    fn test_resolve_shadowed_builtin() {
+        let temp = TempDir::new().unwrap();
+        temp.child("index.js")
+            .write_str("require('punycode/');\nrequire('punycode');\nrequire('node:fs');")
+            .unwrap();
+        temp.child("node_modules/punycode/package.json")
+            .write_str(r#"{"name":"punycode","main":"punycode.js"}"#)
+            .unwrap();
+        temp.child("node_modules/punycode/punycode.js")
+            .write_str("module.exports = {};")
+            .unwrap();
+
+        let path = temp.path().canonicalize().unwrap();
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.join("index.js")],
+                ..Default::default()
+            },
+            &[],
+        );
+
+        let result = visitor.run();
+
+        assert_eq!(
+            result,
+            HashSet::from([
+                path.join("index.js"),
+                path.join("node_modules/punycode/package.json"),
+                path.join("node_modules/punycode/punycode.js"),
+            ])
+        );
+    }
+
+    fn create_hazardous_project() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        temp.child("index.js")
+            .write_str("require('hazard');")
+            .unwrap();
+        temp.child("node_modules/hazard/package.json")
+            .write_str(r#"{"name":"hazard"}"#)
+            .unwrap();
+        temp.child("node_modules/hazard/index.js")
+            .write_str(
+                "module.exports = require(process.env.PLUGIN);\n\
+                 eval('1');\n\
+                 new Function('return 1');\n\
+                 require('vm').runInThisContext('1');\n\
+                 require('module')._load('plugin');\n\
+                 this._load();",
+            )
+            .unwrap();
+        temp.child("node_modules/hazard/lib/plugin.js")
+            .write_str("module.exports = 1;")
+            .unwrap();
+        temp.child("node_modules/hazard/node_modules/nested/index.js")
+            .write_str("module.exports = 1;")
+            .unwrap();
+
+        temp
+    }
+
+    #[test]
+    fn test_hazards() {
+        let temp = create_hazardous_project();
+        let path = temp.path().canonicalize().unwrap();
+        let package = path.join("node_modules/hazard");
+
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.join("index.js")],
+                ..Default::default()
+            },
+            &[],
+        );
+
+        let result = visitor.run();
+
+        assert!(!result.contains(&package.join("lib/plugin.js")));
+        assert_eq!(
+            visitor.hazards,
+            [
+                HazardReason::DynamicRequire,
+                HazardReason::Eval,
+                HazardReason::FunctionConstructor,
+                HazardReason::VmRunInContext,
+                HazardReason::ModuleLoad,
+            ]
+            .into_iter()
+            .map(|reason| Hazard {
+                package: package.clone(),
+                path: package.join("index.js"),
+                reason,
+            })
+            .collect()
+        );
+    }
+
+    #[test]
+    fn test_keep_hazardous() {
+        let temp = create_hazardous_project();
+        let path = temp.path().canonicalize().unwrap();
+        let package = path.join("node_modules/hazard");
+
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.join("index.js")],
+                keep_hazardous: true,
+                ..Default::default()
+            },
+            &[],
+        );
+
+        let result = visitor.run();
+
+        assert_eq!(
+            result,
+            HashSet::from([
+                path.join("index.js"),
+                package.join("package.json"),
+                package.join("index.js"),
+                package.join("lib/plugin.js"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_resolve_bundle_outputs() {
+        let temp = TempDir::new().unwrap();
+        temp.child("index.js").touch().unwrap();
+        temp.child("dist/index.js")
+            .write_str("require(['fastify'].join(''));")
+            .unwrap();
+        temp.child("meta.json")
+            .write_str(
+                r#"{"inputs":{},"outputs":{"dist/index.js":{"imports":[
+                    {"path":"fastify","kind":"require-call","external":true},
+                    {"path":"fs","kind":"require-call","external":true}
+                ]}}}"#,
+            )
+            .unwrap();
+        temp.child("node_modules/fastify/package.json")
+            .write_str(r#"{"name":"fastify","main":"fastify.js"}"#)
+            .unwrap();
+        temp.child("node_modules/fastify/fastify.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/unused/index.js").touch().unwrap();
+
+        let path = temp.path().canonicalize().unwrap();
+        let mut visitor = Visitor::new(
+            &Cli {
+                project_root_location: path.clone(),
+                entry_point_location: vec![path.join("index.js")],
+                metafile_location: Some("meta.json".into()),
+                ..Default::default()
+            },
+            &[],
+        );
+
+        let result = visitor.run();
+
+        assert_eq!(
+            result,
+            HashSet::from([
+                path.join("index.js"),
+                path.join("dist/index.js"),
+                path.join("node_modules/fastify/package.json"),
+                path.join("node_modules/fastify/fastify.js"),
+            ])
+        );
+    }
+
+    fn create_dual_package_project() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        temp.child("index.js")
+            .write_str("require('dual');")
+            .unwrap();
+        temp.child("node_modules/dual/package.json")
+            .write_str(
+                r##"{
+                    "name": "dual",
+                    "main": "./cjs/index.js",
+                    "module": "./esm/index.mjs",
+                    "exports": {
+                        ".": { "import": "./esm/index.mjs", "require": "./cjs/index.js" },
+                        "./feature/*": "./lib/feature/*.js"
+                    },
+                    "imports": { "#internal": "./lib/internal.js", "#dep": "fastify" }
+                }"##,
+            )
+            .unwrap();
+        temp.child("node_modules/dual/cjs/index.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/dual/esm/index.mjs")
+            .write_str("import './shared.mjs';")
+            .unwrap();
+        temp.child("node_modules/dual/esm/shared.mjs")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/dual/lib/feature/a.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/dual/lib/feature/nested/b.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/dual/lib/internal.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/dual/unused.js").touch().unwrap();
+
+        temp
+    }
+
+    #[test]
+    fn test_resolve_without_exports() {
+        let temp = create_dual_package_project();
+        let path = temp.path().canonicalize().unwrap();
+        let package = path.join("node_modules/dual");
+
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.join("index.js")],
+                ..Default::default()
+            },
+            &[],
+        );
+
+        assert_eq!(
+            visitor.run(),
+            HashSet::from([
+                path.join("index.js"),
+                package.join("package.json"),
+                package.join("cjs/index.js"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_resolve_keep_exports() {
+        let temp = create_dual_package_project();
+        let path = temp.path().canonicalize().unwrap();
+        let package = path.join("node_modules/dual");
+
+        let mut visitor = Visitor::new(
+            &Cli {
+                entry_point_location: vec![path.join("index.js")],
+                keep_exports: true,
+                ..Default::default()
+            },
+            &[],
+        );
+
+        assert_eq!(
+            visitor.run(),
+            HashSet::from([
+                path.join("index.js"),
+                package.join("package.json"),
+                package.join("cjs/index.js"),
+                package.join("esm/index.mjs"),
+                package.join("esm/shared.mjs"),
+                package.join("lib/feature/a.js"),
+                package.join("lib/feature/nested/b.js"),
+                package.join("lib/internal.js"),
+            ])
+        );
+    }
 }
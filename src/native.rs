@@ -0,0 +1,146 @@
+//! Pruning of the build residue of native packages
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    configurations::Cli,
+    glob::retrieve_glob_paths,
+    package::{is_in_nested_node_modules, package_root, read_manifest, retrieve_manifests},
+};
+
+/// Build intermediates of node-gyp, relative to the package root
+static BUILD_RESIDUE_ITEMS: &[&str] = &[
+    "build/Makefile",
+    "build/*.mk",
+    "build/config.gypi",
+    "build/gyp-*-tool",
+    "build/Release/.deps",
+    "build/Release/obj",
+    "build/Release/obj.target",
+    "build/Debug",
+    "**/*.gyp",
+    "**/*.gypi",
+    "**/*.o",
+    "**/*.obj",
+    "**/*.a",
+    "**/*.lib",
+];
+
+/// C and C++ sources and headers, relative to the package root
+static NATIVE_SOURCE_ITEMS: &[&str] = &[
+    "**/*.c", "**/*.cc", "**/*.cpp", "**/*.cxx", "**/*.h", "**/*.hh", "**/*.hpp", "**/*.hxx",
+];
+
+/// Whether the package is compiled with node-gyp, through a `binding.gyp` file or a `gypfile` field
+fn is_native_package(package_root: &Path) -> bool {
+    package_root.join("binding.gyp").is_file()
+        || read_manifest(&package_root.join("package.json"))
+            .is_some_and(|manifest| manifest["gypfile"].as_bool() == Some(true))
+}
+
+/// Whether the `.node` addons were already copied out of the `obj.target` directory
+fn has_release_addon(package_root: &Path) -> bool {
+    let addons_glob = package_root.join("build").join("Release").join("*.node");
+
+    !retrieve_glob_paths(vec![addons_glob.display().to_string()]).is_empty()
+}
+
+fn retrieve_package_native_garbage(package_root: &Path) -> Vec<PathBuf> {
+    let keep_obj_target = !has_release_addon(package_root);
+
+    let garbage_glob = BUILD_RESIDUE_ITEMS
+        .iter()
+        .chain(NATIVE_SOURCE_ITEMS)
+        .filter(|item| !(keep_obj_target && item.ends_with("obj.target")))
+        .map(|item| package_root.join(item).display().to_string())
+        .collect();
+
+    let mut garbage: Vec<PathBuf> = retrieve_glob_paths(garbage_glob)
+        .into_iter()
+        .filter(|path| !is_in_nested_node_modules(package_root, path))
+        .filter(|path| path.extension().is_none_or(|extension| extension != "node"))
+        .collect();
+
+    // Directories come before their content, which is removed with them
+    garbage.sort();
+    garbage.dedup_by(|path, dir| path.starts_with(dir));
+    garbage
+}
+
+/// Retrieves the build intermediates and the C/C++ sources of the native packages, keeping their
+/// `.node` addons
+pub fn retrieve_native_garbage(configurations: &Cli) -> Vec<PathBuf> {
+    if !configurations.prune_native {
+        return Vec::new();
+    }
+
+    retrieve_manifests(&configurations.node_modules_location)
+        .into_iter()
+        .filter_map(|manifest_path| manifest_path.parent().map(Path::to_path_buf))
+        .filter(|dir| package_root(dir).as_ref() == Some(dir))
+        .filter(|dir| is_native_package(dir))
+        .flat_map(|dir| retrieve_package_native_garbage(&dir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[test]
+    fn test_retrieve_native_garbage() {
+        let temp = TempDir::new().unwrap();
+        let node_modules = temp.path().canonicalize().unwrap().join("node_modules");
+
+        temp.child("node_modules/bcrypt/package.json")
+            .write_str(r#"{"name":"bcrypt","gypfile":true}"#)
+            .unwrap();
+        temp.child("node_modules/sqlite3/package.json")
+            .write_str(r#"{"name":"sqlite3"}"#)
+            .unwrap();
+        temp.child("node_modules/fastify/package.json")
+            .write_str(r#"{"name":"fastify"}"#)
+            .unwrap();
+
+        for file in [
+            "bcrypt/build/Makefile",
+            "bcrypt/build/bcrypt_lib.target.mk",
+            "bcrypt/build/Release/bcrypt_lib.node",
+            "bcrypt/build/Release/obj.target/bcrypt_lib.node",
+            "bcrypt/build/Release/obj.target/bcrypt_lib/src/bcrypt.o",
+            "bcrypt/src/bcrypt.cc",
+            "bcrypt/bcrypt.js",
+            "bcrypt/node_modules/nested/src/nested.c",
+            "sqlite3/binding.gyp",
+            "sqlite3/deps/sqlite3.gyp",
+            "sqlite3/deps/sqlite-autoconf/sqlite3.h",
+            "sqlite3/build/Release/obj.target/node_sqlite3.node",
+            "fastify/test/fixture.c",
+        ] {
+            temp.child("node_modules").child(file).touch().unwrap();
+        }
+
+        let configurations = Cli {
+            node_modules_location: node_modules.clone(),
+            prune_native: true,
+            ..Default::default()
+        };
+
+        let mut garbage = retrieve_native_garbage(&configurations);
+        garbage.sort();
+
+        assert_eq!(
+            garbage,
+            vec![
+                node_modules.join("bcrypt/build/Makefile"),
+                node_modules.join("bcrypt/build/Release/obj.target"),
+                node_modules.join("bcrypt/build/bcrypt_lib.target.mk"),
+                node_modules.join("bcrypt/src/bcrypt.cc"),
+                node_modules.join("sqlite3/binding.gyp"),
+                node_modules.join("sqlite3/deps/sqlite-autoconf/sqlite3.h"),
+                node_modules.join("sqlite3/deps/sqlite3.gyp"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Node file trace (`*.nft.json`) related code
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::configurations::Cli;
+
+const NFT_EXTENSION: &str = ".nft.json";
+const NODE_MODULES: &str = "node_modules";
+
+/// Parses a `.nft.json` file, returning the traced entry and every file it needs
+///
+/// The listed files are relative to the directory of the trace file.
+fn parse_nft(nft_path: &Path, content: &str) -> Vec<PathBuf> {
+    let nft_dir = nft_path.parent().unwrap_or(Path::new(""));
+
+    match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(nft) => {
+            let entry = nft_path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(|file_name| file_name.strip_suffix(NFT_EXTENSION))
+                .map(|entry| nft_dir.join(entry));
+
+            nft["files"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|file| file.as_str())
+                .map(|file| nft_dir.join(file))
+                .chain(entry)
+                .collect()
+        }
+        Err(error) => {
+            println!("Failed to parse file {}: {}", nft_path.display(), error);
+            Vec::new()
+        }
+    }
+}
+
+/// Collects the `.nft.json` files under a directory
+///
+/// `node_modules` directories are not visited: they are slow to walk, and the traces shipped by
+/// the packages do not describe the application.
+fn collect_nft_files(dir: &Path, nft_files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            if entry.file_name() != NODE_MODULES {
+                collect_nft_files(&path, nft_files);
+            }
+        } else if entry
+            .file_name()
+            .to_str()
+            .is_some_and(|file_name| file_name.ends_with(NFT_EXTENSION))
+        {
+            nft_files.push(path);
+        }
+    }
+}
+
+/// Retrieves every file listed by the `.nft.json` files under the nft location
+pub fn retrieve_nft_paths(configurations: &Cli) -> HashSet<PathBuf> {
+    let nft_location = match &configurations.nft_location {
+        Some(nft_location) => configurations.project_root_location.join(nft_location),
+        None => configurations.project_root_location.clone(),
+    };
+
+    let mut nft_files = Vec::new();
+    collect_nft_files(&nft_location, &mut nft_files);
+
+    nft_files
+        .into_iter()
+        .flat_map(|nft_path| match fs::read_to_string(&nft_path) {
+            Ok(content) => parse_nft(&nft_path, &content),
+            Err(error) => {
+                println!("Failed to read file {}: {}", nft_path.display(), error);
+                Vec::new()
+            }
+        })
+        .filter_map(|path| match path.canonicalize() {
+            Ok(path) => Some(path),
+            Err(err) => {
+                println!("Error while processing {}: {}", path.display(), err);
+                None
+            }
+        })
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[test]
+    fn test_parse_nft() {
+        assert_eq!(
+            parse_nft(
+                Path::new("/app/.next/server/pages/api/hello.js.nft.json"),
+                r#"{"version":1,"files":["../../../../node_modules/next/package.json"]}"#
+            ),
+            vec![
+                PathBuf::from(
+                    "/app/.next/server/pages/api/../../../../node_modules/next/package.json"
+                ),
+                PathBuf::from("/app/.next/server/pages/api/hello.js"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_retrieve_nft_paths() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().canonicalize().unwrap();
+
+        temp.child("server.js").touch().unwrap();
+        temp.child("server.js.nft.json")
+            .write_str(r#"{"version":1,"files":["node_modules/next/dist/server/next.js","node_modules/next/package.json"]}"#)
+            .unwrap();
+        temp.child(".next/server/pages/index.js").touch().unwrap();
+        temp.child(".next/server/pages/index.js.nft.json")
+            .write_str(r#"{"version":1,"files":["../../../node_modules/react/index.js","../../../node_modules/react/missing.js"]}"#)
+            .unwrap();
+        temp.child("node_modules/next/dist/server/next.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/next/dist/server/unused.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/next/package.json")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/react/index.js").touch().unwrap();
+        temp.child("node_modules/react/cjs/react.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/react/index.js.nft.json")
+            .write_str(r#"{"version":1,"files":["cjs/react.js"]}"#)
+            .unwrap();
+
+        let configurations = Cli {
+            project_root_location: path.clone(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            retrieve_nft_paths(&configurations),
+            HashSet::from([
+                path.join("server.js"),
+                path.join(".next/server/pages/index.js"),
+                path.join("node_modules/next/dist/server/next.js"),
+                path.join("node_modules/next/package.json"),
+                path.join("node_modules/react/index.js"),
+            ])
+        );
+    }
+}
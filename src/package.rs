@@ -0,0 +1,552 @@
+//! Package-related code
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{configurations::Cli, glob::retrieve_glob_paths};
+
+const NODE_MODULES: &str = "node_modules";
+const MANIFEST: &str = "package.json";
+
+/// Export conditions that point to type declarations, which are not loaded at runtime
+static TYPE_CONDITIONS: &[&str] = &["types", "typings"];
+
+/// Manifest fields read by Node and the common loaders, like `binary` for node-pre-gyp
+static RUNTIME_MANIFEST_FIELDS: &[&str] = &[
+    "name",
+    "version",
+    "main",
+    "module",
+    "exports",
+    "imports",
+    "type",
+    "browser",
+    "bin",
+    "binary",
+    "dependencies",
+    "optionalDependencies",
+    "engines",
+];
+
+/// Returns the root directory of the package that contains the path
+///
+/// The root is the directory right after the last `node_modules` component (or the last two
+/// components for scoped packages). Paths outside of `node_modules` have no package root.
+pub fn package_root(path: &Path) -> Option<PathBuf> {
+    let components: Vec<Component> = path.components().collect();
+    let node_modules_index = components
+        .iter()
+        .rposition(|component| component.as_os_str() == NODE_MODULES)?;
+    let package_name = components.get(node_modules_index + 1)?;
+
+    let root_length = if package_name.as_os_str().to_string_lossy().starts_with('@') {
+        node_modules_index + 3
+    } else {
+        node_modules_index + 2
+    };
+
+    if components.len() < root_length {
+        return None;
+    }
+
+    Some(components[..root_length].iter().collect())
+}
+
+/// Returns the package name of a directory, like `@fastify/busboy`
+pub fn package_name(dir: &Path) -> Option<String> {
+    let components: Vec<Component> = dir.components().collect();
+    let node_modules_index = components
+        .iter()
+        .rposition(|component| component.as_os_str() == NODE_MODULES)?;
+
+    let name: Vec<String> = components[node_modules_index + 1..]
+        .iter()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    (!name.is_empty()).then(|| name.join("/"))
+}
+
+/// Whether the path goes through a nested `node_modules` directory below the package root
+pub fn is_in_nested_node_modules(package_root: &Path, path: &Path) -> bool {
+    path.strip_prefix(package_root).is_ok_and(|relative_path| {
+        relative_path
+            .components()
+            .any(|component| component.as_os_str() == NODE_MODULES)
+    })
+}
+
+/// Reads and parses a `package.json` file
+pub fn read_manifest(path: &Path) -> Option<Value> {
+    match fs::read_to_string(path) {
+        Ok(manifest) => match serde_json::from_str(&manifest) {
+            Ok(manifest) => Some(manifest),
+            Err(error) => {
+                println!("Failed to parse file {}: {}", path.display(), error);
+                None
+            }
+        },
+        Err(error) => {
+            println!("Failed to read file {}: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+/// Collects every string target of a manifest field, for every condition and subpath
+pub fn collect_targets(field: &Value) -> Vec<String> {
+    match field {
+        Value::String(target) => vec![target.clone()],
+        Value::Array(targets) => targets.iter().flat_map(collect_targets).collect(),
+        Value::Object(targets) => targets.values().flat_map(collect_targets).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Collects the targets of a manifest field, skipping the type declarations of the `types` and
+/// `typings` conditions
+fn collect_runtime_targets(field: &Value) -> Vec<String> {
+    match field {
+        Value::String(target) => vec![target.clone()],
+        Value::Array(targets) => targets.iter().flat_map(collect_runtime_targets).collect(),
+        Value::Object(targets) => targets
+            .iter()
+            .filter(|(key, _)| !TYPE_CONDITIONS.contains(&key.as_str()))
+            .flat_map(|(_, target)| collect_runtime_targets(target))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Collects the targets of a manifest field that sit below the given condition, like `require`
+pub fn collect_condition_targets(field: &Value, condition: &str) -> Vec<String> {
+    match field {
+        Value::Array(targets) => targets
+            .iter()
+            .flat_map(|target| collect_condition_targets(target, condition))
+            .collect(),
+        Value::Object(targets) => targets
+            .iter()
+            .flat_map(|(key, target)| {
+                if key == condition {
+                    collect_targets(target)
+                } else {
+                    collect_condition_targets(target, condition)
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Retrieves every `package.json` file under the `node_modules` directory
+pub fn retrieve_manifests(node_modules_location: &Path) -> Vec<PathBuf> {
+    let manifests_glob = node_modules_location.join("**").join(MANIFEST);
+
+    retrieve_glob_paths(vec![manifests_glob.display().to_string()])
+        .into_iter()
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Retrieves the files referenced by the `main`, `module`, `exports`, `bin` and `imports` fields
+/// of every manifest under the `node_modules` directory, type declarations excluded
+pub fn retrieve_referenced_paths(node_modules_location: &Path) -> HashSet<PathBuf> {
+    retrieve_manifests(node_modules_location)
+        .into_iter()
+        .flat_map(|manifest_path| {
+            let (Some(manifest), Some(package_root)) =
+                (read_manifest(&manifest_path), manifest_path.parent())
+            else {
+                return Vec::new();
+            };
+
+            ["main", "module", "exports", "bin", "imports"]
+                .iter()
+                .flat_map(|field| collect_runtime_targets(&manifest[field]))
+                .flat_map(|target| resolve_target(package_root, &target))
+                .filter_map(|path| path.canonicalize().ok())
+                .collect()
+        })
+        .collect()
+}
+
+/// Resolves a manifest target to the files it refers to, relative to the package root
+///
+/// Targets with a `*` pattern match every file they could expand to, other targets are resolved
+/// with the same extension lookup Node.js uses for `main`.
+pub fn resolve_target(package_root: &Path, target: &str) -> Vec<PathBuf> {
+    if target.contains('*') {
+        let target_glob = package_root.join(target.replace('*', "**/*"));
+
+        return retrieve_glob_paths(vec![target_glob.display().to_string()])
+            .into_iter()
+            .filter(|path| path.is_file())
+            .collect();
+    }
+
+    let target_path = package_root.join(target);
+    let target_location = target_path.display();
+
+    [
+        target_path.clone(),
+        PathBuf::from(format!("{target_location}.js")),
+        PathBuf::from(format!("{target_location}.json")),
+        PathBuf::from(format!("{target_location}.node")),
+        target_path.join("index.js"),
+        target_path.join("index.json"),
+        target_path.join("index.node"),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+    .into_iter()
+    .collect()
+}
+
+/// Removes the targets of an `exports` field that do not exist anymore
+///
+/// `null` targets are kept, since they exclude subpaths on purpose. Returns `None` when no
+/// target is left.
+fn prune_exports(package_root: &Path, field: &Value) -> Option<Value> {
+    match field {
+        Value::String(target) if target.contains('*') => {
+            (!resolve_target(package_root, target).is_empty()).then(|| field.clone())
+        }
+        Value::String(target) => package_root.join(target).exists().then(|| field.clone()),
+        Value::Array(targets) => {
+            let targets: Vec<Value> = targets
+                .iter()
+                .filter_map(|target| prune_exports(package_root, target))
+                .collect();
+
+            (!targets.is_empty()).then_some(Value::Array(targets))
+        }
+        Value::Object(targets) => {
+            let targets: serde_json::Map<String, Value> = targets
+                .iter()
+                .filter_map(|(key, target)| {
+                    prune_exports(package_root, target).map(|target| (key.clone(), target))
+                })
+                .collect();
+
+            (!targets.is_empty()).then_some(Value::Object(targets))
+        }
+        _ => Some(field.clone()),
+    }
+}
+
+/// Removes the manifest targets that do not exist anymore, returning whether it changed
+///
+/// The `exports` map only keeps the existing targets, so removed subpaths fail with
+/// `ERR_PACKAGE_PATH_NOT_EXPORTED`. Missing `main`, `module` and `browser` entries are dropped.
+pub fn prune_manifest(package_root: &Path, manifest: &mut Value) -> bool {
+    let Some(fields) = manifest.as_object_mut() else {
+        return false;
+    };
+    let mut changed = false;
+
+    if let Some(exports) = fields
+        .get_mut("exports")
+        .filter(|exports| !exports.is_null())
+    {
+        let pruned_exports = prune_exports(package_root, exports)
+            .unwrap_or_else(|| Value::Object(serde_json::Map::new()));
+
+        if *exports != pruned_exports {
+            *exports = pruned_exports;
+            changed = true;
+        }
+    }
+
+    for field in ["main", "module", "browser"] {
+        let is_missing = fields
+            .get(field)
+            .and_then(Value::as_str)
+            .is_some_and(|target| resolve_target(package_root, target).is_empty());
+
+        if is_missing {
+            fields.shift_remove(field);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// Retrieves the manifests of the packages that own the removed paths, the removed packages
+/// excluded
+pub fn retrieve_pruned_manifests(removed_paths: &[PathBuf]) -> BTreeSet<PathBuf> {
+    removed_paths
+        .iter()
+        .filter_map(|path| {
+            path.ancestors()
+                .skip(1)
+                .take_while(|dir| dir.file_name().is_some_and(|name| name != NODE_MODULES))
+                .map(|dir| dir.join(MANIFEST))
+                .find(|manifest_path| manifest_path.is_file())
+        })
+        .collect()
+}
+
+/// Rewrites the manifests of the packages that lost files so that they only point to existing
+/// files
+pub fn rewrite_manifests(removed_paths: &[PathBuf]) {
+    for manifest_path in retrieve_pruned_manifests(removed_paths) {
+        let (Some(mut manifest), Some(package_root)) =
+            (read_manifest(&manifest_path), manifest_path.parent())
+        else {
+            continue;
+        };
+
+        if !prune_manifest(package_root, &mut manifest) {
+            continue;
+        }
+
+        let manifest_location = manifest_path.display();
+
+        match write_manifest(&manifest_path, &manifest) {
+            Ok(()) => println!("Rewritten: {manifest_location}"),
+            Err(err) => println!("Failed to rewrite: {manifest_location}, {err}"),
+        }
+    }
+}
+
+fn write_manifest(manifest_path: &Path, manifest: &Value) -> std::io::Result<()> {
+    let content = serde_json::to_string_pretty(manifest).unwrap_or_default();
+
+    fs::write(manifest_path, content + "\n")
+}
+
+/// Removes the manifest fields that are not used at runtime, keeping the order of the other ones
+pub fn slim_manifest(manifest: &mut Value) -> bool {
+    let Some(fields) = manifest.as_object_mut() else {
+        return false;
+    };
+    let fields_count = fields.len();
+
+    fields.retain(|field, _| RUNTIME_MANIFEST_FIELDS.contains(&field.as_str()));
+
+    fields.len() != fields_count
+}
+
+/// Rewrites the manifests under the `node_modules` directory so that they only keep the fields
+/// used at runtime
+pub fn slim_manifests(configurations: &Cli) {
+    for manifest_path in retrieve_manifests(&configurations.node_modules_location) {
+        let Some(mut manifest) = read_manifest(&manifest_path) else {
+            continue;
+        };
+
+        if !slim_manifest(&mut manifest) {
+            continue;
+        }
+
+        let manifest_location = manifest_path.display();
+
+        match write_manifest(&manifest_path, &manifest) {
+            Ok(()) => println!("Slimmed: {manifest_location}"),
+            Err(err) => println!("Failed to slim: {manifest_location}, {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[test]
+    fn test_package_root() {
+        assert_eq!(
+            package_root(Path::new("/app/node_modules/fastify/lib/server.js")),
+            Some(PathBuf::from("/app/node_modules/fastify"))
+        );
+        assert_eq!(
+            package_root(Path::new("/app/node_modules/@fastify/busboy/lib/main.js")),
+            Some(PathBuf::from("/app/node_modules/@fastify/busboy"))
+        );
+        assert_eq!(
+            package_root(Path::new(
+                "/app/node_modules/fastify/node_modules/pino/index.js"
+            )),
+            Some(PathBuf::from("/app/node_modules/fastify/node_modules/pino"))
+        );
+        assert_eq!(package_root(Path::new("/app/node_modules/@fastify")), None);
+        assert_eq!(package_root(Path::new("/app/dist/index.js")), None);
+    }
+
+    #[test]
+    fn test_is_in_nested_node_modules() {
+        let root = Path::new("/app/node_modules/fastify");
+
+        assert!(is_in_nested_node_modules(
+            root,
+            Path::new("/app/node_modules/fastify/node_modules/pino/index.js")
+        ));
+        assert!(!is_in_nested_node_modules(
+            root,
+            Path::new("/app/node_modules/fastify/lib/server.js")
+        ));
+    }
+
+    #[test]
+    fn test_collect_condition_targets() {
+        let exports = serde_json::json!({
+            ".": {
+                "import": { "types": "./esm/index.d.mts", "default": "./esm/index.mjs" },
+                "require": "./cjs/index.js"
+            },
+            "./feature": [{ "import": "./esm/feature.mjs" }, "./cjs/feature.js"]
+        });
+
+        let mut targets = collect_condition_targets(&exports, "import");
+        targets.sort();
+
+        assert_eq!(
+            targets,
+            vec!["./esm/feature.mjs", "./esm/index.d.mts", "./esm/index.mjs"]
+        );
+        assert_eq!(
+            collect_condition_targets(&exports, "require"),
+            vec!["./cjs/index.js"]
+        );
+    }
+
+    #[test]
+    fn test_collect_targets() {
+        let exports = serde_json::json!({
+            ".": {
+                "node": { "import": "./esm/index.mjs", "require": "./cjs/index.js" },
+                "default": ["./browser.js", null]
+            },
+            "./feature/*": "./lib/feature/*.js",
+            "./internal/*": null
+        });
+
+        let mut targets = collect_targets(&exports);
+        targets.sort();
+
+        assert_eq!(
+            targets,
+            vec![
+                "./browser.js",
+                "./cjs/index.js",
+                "./esm/index.mjs",
+                "./lib/feature/*.js",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_runtime_targets() {
+        let exports = serde_json::json!({
+            ".": {
+                "types": "./index.d.ts",
+                "import": { "types": "./esm/index.d.mts", "default": "./esm/index.mjs" },
+                "require": "./cjs/index.js"
+            },
+            "./feature": { "typings": "./feature.d.ts", "default": "./feature.js" }
+        });
+
+        let mut targets = collect_runtime_targets(&exports);
+        targets.sort();
+
+        assert_eq!(
+            targets,
+            vec!["./cjs/index.js", "./esm/index.mjs", "./feature.js"]
+        );
+    }
+
+    #[test]
+    fn test_prune_manifest() {
+        let temp = TempDir::new().unwrap();
+
+        temp.child("cjs/index.js").touch().unwrap();
+        temp.child("lib/feature/a.js").touch().unwrap();
+
+        let mut manifest = serde_json::json!({
+            "name": "trees",
+            "main": "./cjs/index",
+            "module": "./esm/index.mjs",
+            "browser": "./browser.js",
+            "exports": {
+                ".": {
+                    "import": "./esm/index.mjs",
+                    "require": "./cjs/index.js"
+                },
+                "./feature/*": "./lib/feature/*.js",
+                "./utils/*": "./lib/utils/*.js",
+                "./internal/*": null,
+                "./legacy": ["./legacy.js", "./cjs/index.js"]
+            },
+            "version": "1.0.0"
+        });
+
+        assert!(prune_manifest(temp.path(), &mut manifest));
+        assert_eq!(
+            serde_json::to_string(&manifest).unwrap(),
+            r#"{"name":"trees","main":"./cjs/index","exports":{".":{"require":"./cjs/index.js"},"./feature/*":"./lib/feature/*.js","./internal/*":null,"./legacy":["./cjs/index.js"]},"version":"1.0.0"}"#
+        );
+        assert!(!prune_manifest(temp.path(), &mut manifest));
+    }
+
+    #[test]
+    fn test_retrieve_pruned_manifests() {
+        let temp = TempDir::new().unwrap();
+        let node_modules = temp.child("node_modules");
+
+        node_modules.child("trees/package.json").touch().unwrap();
+        node_modules.child("trees/lib/index.js").touch().unwrap();
+        node_modules
+            .child(".prisma/client/package.json")
+            .touch()
+            .unwrap();
+        node_modules.child("intact/package.json").touch().unwrap();
+        node_modules
+            .child("intact/node_modules/nested/index.js")
+            .touch()
+            .unwrap();
+
+        let removed_paths = vec![
+            node_modules.join("trees/lib/esm/index.mjs"),
+            node_modules.join("trees/README.md"),
+            node_modules.join(".prisma/client/libquery_engine-darwin.dylib.node"),
+            node_modules.join("intact/node_modules/nested/README.md"),
+            node_modules.join("removed"),
+        ];
+
+        assert_eq!(
+            retrieve_pruned_manifests(&removed_paths),
+            BTreeSet::from([
+                node_modules.join(".prisma/client/package.json"),
+                node_modules.join("trees/package.json"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_slim_manifest() {
+        let mut manifest = serde_json::json!({
+            "version": "1.0.0",
+            "name": "trees",
+            "readme": "# trees",
+            "type": "module",
+            "scripts": { "test": "node --test" },
+            "exports": { "import": "./index.mjs", "require": "./index.cjs" },
+            "_resolved": "https://registry.npmjs.org/trees/-/trees-1.0.0.tgz",
+            "dependencies": { "leaves": "^1.0.0" }
+        });
+
+        assert!(slim_manifest(&mut manifest));
+        assert_eq!(
+            serde_json::to_string(&manifest).unwrap(),
+            r#"{"version":"1.0.0","name":"trees","type":"module","exports":{"import":"./index.mjs","require":"./index.cjs"},"dependencies":{"leaves":"^1.0.0"}}"#
+        );
+        assert!(!slim_manifest(&mut manifest));
+    }
+}
@@ -0,0 +1,380 @@
+//! Pruning of the binaries built for other platforms
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    configurations::{Cli, Libc},
+    glob::retrieve_glob_paths,
+    package::{package_name, package_root, read_manifest},
+};
+
+const WINDOWS: &str = "win32";
+const LINUX: &str = "linux";
+const PREBUILDS: &str = "prebuilds";
+
+/// Extensions of the files that only run on Windows
+static WINDOWS_EXTENSIONS: &[&str] = &["exe", "dll", "cmd"];
+
+/// Prefixes of the Prisma engine binaries, followed by their binary target
+static ENGINE_PREFIXES: &[&str] = &[
+    "libquery_engine-",
+    "query_engine-",
+    "query-engine-",
+    "schema-engine-",
+    "migration-engine-",
+    "introspection-engine-",
+];
+
+/// Suffixes of the Prisma engine binaries, after their binary target
+static ENGINE_SUFFIXES: &[&str] = &[".so.node", ".dylib.node", ".dll.node", ".exe", ".node"];
+
+/// Packages (or scopes) that ship the Prisma engines
+static ENGINE_PACKAGES: &[&str] = &["prisma", "@prisma", ".prisma"];
+
+/// Prisma binary targets with their platform, arch and libc, the first matching prefix wins
+static ENGINE_TARGETS: &[(&str, &str, &str, Option<&str>)] = &[
+    ("windows", WINDOWS, "x64", None),
+    ("darwin-arm64", "darwin", "arm64", None),
+    ("darwin", "darwin", "x64", None),
+    ("debian-openssl-", LINUX, "x64", Some("glibc")),
+    ("rhel-openssl-", LINUX, "x64", Some("glibc")),
+    ("linux-nixos", LINUX, "x64", Some("glibc")),
+    ("linux-arm64-openssl-", LINUX, "arm64", Some("glibc")),
+    ("linux-arm-openssl-", LINUX, "arm", Some("glibc")),
+    ("linux-musl-arm64-openssl-", LINUX, "arm64", Some("musl")),
+    ("linux-musl", LINUX, "x64", Some("musl")),
+    ("linux-static-arm64", LINUX, "arm64", None),
+    ("linux-static-x64", LINUX, "x64", None),
+];
+
+/// Platform that the `node_modules` directory is going to run on
+#[derive(Debug, Default)]
+struct Target<'a> {
+    platform: Option<&'a str>,
+    arch: Option<&'a str>,
+    libc: Option<String>,
+}
+
+impl<'a> Target<'a> {
+    fn from_configurations(configurations: &'a Cli) -> Self {
+        Target {
+            platform: configurations.platform.as_deref(),
+            arch: configurations.arch.as_deref(),
+            libc: configurations.libc.as_ref().map(Libc::to_string),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.platform.is_none() && self.arch.is_none() && self.libc.is_none()
+    }
+
+    /// The libc only matters on Linux
+    fn libc(&self) -> Option<&str> {
+        self.libc
+            .as_deref()
+            .filter(|_| self.platform.is_none_or(|platform| platform == LINUX))
+    }
+
+    /// Whether the target matches the given platform, arch and libc, where `None` matches any
+    fn matches(&self, platform: Option<&str>, arch: Option<&str>, libc: Option<&str>) -> bool {
+        [
+            (self.platform, platform),
+            (self.arch, arch),
+            (self.libc(), libc),
+        ]
+        .iter()
+        .all(|pair| match pair {
+            (Some(expected), Some(actual)) => expected == actual,
+            _ => true,
+        })
+    }
+}
+
+/// Whether a `package.json` `os`, `cpu` or `libc` list accepts the value, following npm:
+/// `!value` entries exclude a value, while other entries restrict the accepted ones
+fn accepts(list: &Value, value: &str) -> bool {
+    let entries: Vec<&str> = match list {
+        Value::String(entry) => vec![entry.as_str()],
+        Value::Array(entries) => entries.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+
+    let (excluded, accepted): (Vec<&str>, Vec<&str>) =
+        entries.iter().partition(|entry| entry.starts_with('!'));
+
+    !excluded.iter().any(|entry| entry[1..] == *value)
+        && (accepted.is_empty() || accepted.contains(&value))
+}
+
+/// Whether the manifest `os`, `cpu` and `libc` fields accept the target
+fn supports_target(manifest: &Value, target: &Target) -> bool {
+    [
+        ("os", target.platform),
+        ("cpu", target.arch),
+        ("libc", target.libc()),
+    ]
+    .iter()
+    .all(|(field, value)| match (manifest.get(field), value) {
+        (Some(list), Some(value)) => accepts(list, value),
+        _ => true,
+    })
+}
+
+/// Whether a prebuild directory (like `prebuilds/darwin-x64+arm64`) is built for the target
+fn is_prebuild_for_target(dir_name: &str, target: &Target) -> bool {
+    let (platform, archs) = dir_name.split_once('-').unwrap_or((dir_name, ""));
+
+    archs.split('+').any(|arch| {
+        target.matches(
+            Some(platform),
+            Some(arch).filter(|arch| !arch.is_empty()),
+            None,
+        )
+    })
+}
+
+/// Returns the libc that a prebuild file (like `node.napi.musl.node`) is tagged with
+fn prebuild_libc(file_name: &str) -> Option<&str> {
+    file_name
+        .split('.')
+        .find(|part| *part == Libc::Glibc.to_string() || *part == Libc::Musl.to_string())
+}
+
+/// Whether a Prisma engine (like `libquery_engine-linux-musl-openssl-3.0.x.so.node`) is built
+/// for the target, or `None` if the file is not an engine
+///
+/// Engines with an unknown binary target are considered built for the target, so they are kept.
+fn is_engine_for_target(file_name: &str, target: &Target) -> Option<bool> {
+    let binary_target = ENGINE_PREFIXES
+        .iter()
+        .find_map(|prefix| file_name.strip_prefix(prefix))?;
+    let binary_target = ENGINE_SUFFIXES
+        .iter()
+        .find_map(|suffix| binary_target.strip_suffix(suffix))
+        .unwrap_or(binary_target);
+
+    let is_for_target = ENGINE_TARGETS
+        .iter()
+        .find(|(prefix, ..)| binary_target.starts_with(prefix))
+        .is_none_or(|(_, platform, arch, libc)| target.matches(Some(platform), Some(arch), *libc));
+
+    Some(is_for_target)
+}
+
+/// Whether the path is inside a package that ships the Prisma engines, like `@prisma/engines`
+fn is_in_engine_package(path: &Path) -> bool {
+    path.parent().and_then(package_name).is_some_and(|name| {
+        name.split('/')
+            .next()
+            .is_some_and(|scope| ENGINE_PACKAGES.contains(&scope))
+    })
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .unwrap_or_default()
+}
+
+/// Whether the path is garbage for the target, excluding the packages' `os`, `cpu` and `libc`
+fn is_binary_garbage(path: &Path, target: &Target) -> bool {
+    let parent_name = path.parent().map(file_name).unwrap_or_default();
+    let grandparent_name = path
+        .parent()
+        .and_then(Path::parent)
+        .map(file_name)
+        .unwrap_or_default();
+
+    if path.is_dir() {
+        return parent_name == PREBUILDS && !is_prebuild_for_target(file_name(path), target);
+    }
+
+    if grandparent_name == PREBUILDS {
+        if let (Some(libc), Some(target_libc)) = (prebuild_libc(file_name(path)), target.libc()) {
+            return libc != target_libc;
+        }
+    }
+
+    if is_in_engine_package(path) {
+        if let Some(is_for_target) = is_engine_for_target(file_name(path), target) {
+            return !is_for_target;
+        }
+    }
+
+    let is_windows_only = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            WINDOWS_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        });
+
+    is_windows_only && target.platform.is_some_and(|platform| platform != WINDOWS)
+}
+
+/// Retrieves the packages, prebuilds and engines that are built for other platforms than the
+/// `--platform`, `--arch` and `--libc` target
+pub fn retrieve_platform_garbage(configurations: &Cli) -> Vec<PathBuf> {
+    let target = Target::from_configurations(configurations);
+
+    if target.is_empty() {
+        return Vec::new();
+    }
+
+    let paths = retrieve_glob_paths(vec![configurations
+        .node_modules_location
+        .join("**")
+        .join("*")
+        .display()
+        .to_string()]);
+
+    let unsupported_packages: HashSet<PathBuf> = paths
+        .iter()
+        .filter(|path| file_name(path) == "package.json")
+        .filter_map(|path| path.parent())
+        .filter(|dir| package_root(dir).as_deref() == Some(*dir))
+        .filter(|dir| {
+            read_manifest(&dir.join("package.json"))
+                .is_some_and(|manifest| !supports_target(&manifest, &target))
+        })
+        .map(Path::to_path_buf)
+        .collect();
+
+    let mut garbage: Vec<PathBuf> = paths
+        .into_iter()
+        .filter(|path| unsupported_packages.contains(path) || is_binary_garbage(path, &target))
+        .collect();
+
+    garbage.sort();
+    garbage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[test]
+    fn test_accepts() {
+        let list = serde_json::json!(["linux", "darwin"]);
+        assert!(accepts(&list, "linux"));
+        assert!(!accepts(&list, "win32"));
+
+        let list = serde_json::json!(["!win32"]);
+        assert!(accepts(&list, "linux"));
+        assert!(!accepts(&list, "win32"));
+    }
+
+    #[test]
+    fn test_is_engine_for_target() {
+        let target = Target {
+            platform: Some("linux"),
+            arch: Some("x64"),
+            libc: Some("musl".to_owned()),
+        };
+
+        assert_eq!(
+            is_engine_for_target("libquery_engine-linux-musl-openssl-3.0.x.so.node", &target),
+            Some(true)
+        );
+        assert_eq!(
+            is_engine_for_target("libquery_engine-debian-openssl-3.0.x.so.node", &target),
+            Some(false)
+        );
+        assert_eq!(
+            is_engine_for_target("schema-engine-linux-musl-arm64-openssl-3.0.x", &target),
+            Some(false)
+        );
+        assert_eq!(
+            is_engine_for_target("query_engine-windows.dll.node", &target),
+            Some(false)
+        );
+        assert_eq!(
+            is_engine_for_target("libquery_engine-linux-ppc64le.so.node", &target),
+            Some(true)
+        );
+        assert_eq!(is_engine_for_target("index.js", &target), None);
+    }
+
+    #[test]
+    fn test_retrieve_platform_garbage() {
+        let temp = TempDir::new().unwrap();
+        let node_modules = temp.path().canonicalize().unwrap().join("node_modules");
+
+        for (manifest, content) in [
+            (
+                "@esbuild/darwin-arm64",
+                r#"{"os":["darwin"],"cpu":["arm64"]}"#,
+            ),
+            ("@esbuild/linux-x64", r#"{"os":["linux"],"cpu":["x64"]}"#),
+            (
+                "@img/sharp-linux-x64",
+                r#"{"os":["linux"],"cpu":["x64"],"libc":["glibc"]}"#,
+            ),
+            (
+                "@img/sharp-linuxmusl-x64",
+                r#"{"os":["linux"],"cpu":["x64"],"libc":["musl"]}"#,
+            ),
+            ("fsevents", r#"{"os":["darwin"]}"#),
+        ] {
+            temp.child("node_modules")
+                .child(manifest)
+                .child("package.json")
+                .write_str(content)
+                .unwrap();
+        }
+
+        for file in [
+            "classic-level/prebuilds/win32-x64/node.napi.node",
+            "classic-level/prebuilds/darwin-x64+arm64/node.napi.node",
+            "classic-level/prebuilds/linux-x64/node.napi.glibc.node",
+            "classic-level/prebuilds/linux-x64/node.napi.musl.node",
+            ".prisma/client/libquery_engine-darwin-arm64.dylib.node",
+            ".prisma/client/libquery_engine-debian-openssl-3.0.x.so.node",
+            ".prisma/client/libquery_engine-linux-musl-openssl-3.0.x.so.node",
+            "@prisma/engines/schema-engine-darwin",
+            "other/query-engine-darwin",
+            "tool/bin/tool.exe",
+            "tool/bin/tool.cmd",
+            "tool/bin/tool.js",
+        ] {
+            temp.child("node_modules").child(file).touch().unwrap();
+        }
+
+        let configurations = Cli {
+            node_modules_location: node_modules.clone(),
+            platform: Some("linux".to_owned()),
+            arch: Some("x64".to_owned()),
+            libc: Some(Libc::Glibc),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            retrieve_platform_garbage(&configurations),
+            vec![
+                node_modules.join(".prisma/client/libquery_engine-darwin-arm64.dylib.node"),
+                node_modules
+                    .join(".prisma/client/libquery_engine-linux-musl-openssl-3.0.x.so.node"),
+                node_modules.join("@esbuild/darwin-arm64"),
+                node_modules.join("@img/sharp-linuxmusl-x64"),
+                node_modules.join("@prisma/engines/schema-engine-darwin"),
+                node_modules.join("classic-level/prebuilds/darwin-x64+arm64"),
+                node_modules.join("classic-level/prebuilds/linux-x64/node.napi.musl.node"),
+                node_modules.join("classic-level/prebuilds/win32-x64"),
+                node_modules.join("fsevents"),
+                node_modules.join("tool/bin/tool.cmd"),
+                node_modules.join("tool/bin/tool.exe"),
+            ]
+        );
+
+        assert!(retrieve_platform_garbage(&Cli {
+            node_modules_location: node_modules,
+            ..Default::default()
+        })
+        .is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! Built-in keep and garbage rules for well-known packages
+
+use std::path::PathBuf;
+
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+
+use crate::{
+    configurations::Cli,
+    package::{package_name, retrieve_manifests},
+};
+
+const PRESETS: &str = include_str!("presets.toml");
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Debug, Deserialize)]
+struct Presets {
+    version: u32,
+    presets: Vec<Preset>,
+}
+
+/// Rules for the packages whose name matches `package`, relative to the package root
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Preset {
+    pub package: String,
+    #[serde(default)]
+    pub keep: Vec<String>,
+    #[serde(default)]
+    pub garbage: Vec<String>,
+}
+
+/// # Panics if the embedded presets are invalid
+fn load_presets() -> Presets {
+    toml::from_str(PRESETS).expect("Failed to parse the embedded presets")
+}
+
+/// Retrieves the installed packages that have a preset, with their directory
+pub fn retrieve_applied_presets(configurations: &Cli) -> Vec<(PathBuf, Preset)> {
+    if configurations.no_presets {
+        return Vec::new();
+    }
+
+    let presets: Vec<(Pattern, Preset)> = load_presets()
+        .presets
+        .into_iter()
+        .filter_map(|preset| {
+            Pattern::new(&preset.package)
+                .ok()
+                .map(|pattern| (pattern, preset))
+        })
+        .collect();
+
+    let mut applied_presets: Vec<(PathBuf, Preset)> =
+        retrieve_manifests(&configurations.node_modules_location)
+            .into_iter()
+            .filter_map(|manifest_path| manifest_path.parent().map(PathBuf::from))
+            .flat_map(|dir| {
+                let name = package_name(&dir).unwrap_or_default();
+
+                presets
+                    .iter()
+                    .filter(move |(pattern, _)| pattern.matches_with(&name, MATCH_OPTIONS))
+                    .map(move |(_, preset)| (dir.clone(), preset.clone()))
+            })
+            .collect();
+
+    applied_presets.sort_by(|(a, _), (b, _)| a.cmp(b));
+    applied_presets
+}
+
+/// Returns the absolute keep patterns of the applied presets
+pub fn keep_patterns(applied_presets: &[(PathBuf, Preset)]) -> Vec<String> {
+    applied_presets
+        .iter()
+        .flat_map(|(dir, preset)| {
+            preset
+                .keep
+                .iter()
+                .map(move |pattern| dir.join(pattern).display().to_string())
+        })
+        .collect()
+}
+
+/// Returns the absolute garbage patterns of the applied presets
+pub fn garbage_patterns(applied_presets: &[(PathBuf, Preset)]) -> Vec<String> {
+    applied_presets
+        .iter()
+        .flat_map(|(dir, preset)| {
+            preset
+                .garbage
+                .iter()
+                .map(move |pattern| dir.join(pattern).display().to_string())
+        })
+        .collect()
+}
+
+/// Prints the presets applied to the installed packages
+pub fn report_presets(applied_presets: &[(PathBuf, Preset)]) {
+    if applied_presets.is_empty() {
+        return;
+    }
+
+    println!("Applied presets (version {}):", load_presets().version);
+    for (dir, preset) in applied_presets {
+        println!("{}: {}", preset.package, dir.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[test]
+    fn test_load_presets() {
+        let presets = load_presets();
+
+        assert!(presets.version > 0);
+        for preset in presets.presets {
+            assert!(Pattern::new(&preset.package).is_ok());
+            assert!(preset
+                .keep
+                .iter()
+                .chain(&preset.garbage)
+                .all(|pattern| Pattern::new(pattern).is_ok()));
+        }
+    }
+
+    #[test]
+    fn test_retrieve_applied_presets() {
+        let temp = TempDir::new().unwrap();
+        let node_modules = temp.path().canonicalize().unwrap().join("node_modules");
+
+        for manifest in [
+            "ajv/package.json",
+            "fastify/package.json",
+            "@tryghost/errors/package.json",
+            "@tryghost/errors/node_modules/ajv/package.json",
+            ".prisma/client/package.json",
+        ] {
+            temp.child("node_modules")
+                .child(manifest)
+                .write_str("{}")
+                .unwrap();
+        }
+
+        let mut configurations = Cli {
+            node_modules_location: node_modules.clone(),
+            ..Default::default()
+        };
+
+        let applied_presets: Vec<(PathBuf, String)> = retrieve_applied_presets(&configurations)
+            .into_iter()
+            .map(|(dir, preset)| (dir, preset.package))
+            .collect();
+
+        assert_eq!(
+            applied_presets,
+            vec![
+                (node_modules.join(".prisma/client"), ".prisma/client".into()),
+                (node_modules.join("@tryghost/errors"), "@tryghost/*".into()),
+                (
+                    node_modules.join("@tryghost/errors/node_modules/ajv"),
+                    "ajv".into()
+                ),
+                (node_modules.join("ajv"), "ajv".into()),
+            ]
+        );
+        assert!(keep_patterns(&retrieve_applied_presets(&configurations))
+            .contains(&node_modules.join("ajv/lib/refs/*.*").display().to_string()));
+
+        configurations.no_presets = true;
+
+        assert!(retrieve_applied_presets(&configurations).is_empty());
+    }
+}
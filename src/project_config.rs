@@ -0,0 +1,207 @@
+//! Project configuration file
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{configurations::Cli, package::read_manifest};
+
+const CONFIG_FILE_NAME: &str = "nmt.toml";
+const MANIFEST_CONFIG_KEY: &str = "nmt";
+
+/// Garbage rules read from `nmt.toml` or from the `nmt` key of the project `package.json`
+///
+/// ```toml
+/// garbage = ["*.flow"]
+/// disable = ["browser"]
+///
+/// [packages]
+/// lodash = ["**/*.md"]
+/// ```
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Patterns to remove from every package, on top of the built-in ones
+    pub garbage: Vec<String>,
+    /// Whether `garbage` replaces the built-in patterns
+    pub replace: bool,
+    /// Built-in patterns that should not be applied
+    pub disable: Vec<String>,
+    /// Patterns to remove from a single package, relative to its root
+    pub packages: BTreeMap<String, Vec<String>>,
+}
+
+impl ProjectConfig {
+    /// Loads the configuration file, or the default one when the project has none
+    ///
+    /// Without an explicit location, `nmt.toml` is preferred over the `nmt` key of `package.json`.
+    pub fn from_configurations(configurations: &Cli) -> Self {
+        let project_root = &configurations.project_root_location;
+
+        let config_location = configurations
+            .config_location
+            .as_ref()
+            .map(|location| project_root.join(location))
+            .or_else(|| Some(project_root.join(CONFIG_FILE_NAME)).filter(|path| path.is_file()));
+
+        match config_location {
+            Some(config_location) => Self::read(&config_location),
+            None => Self::read_manifest_key(&project_root.join("package.json")),
+        }
+        .unwrap_or_default()
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        if path.file_name().is_some_and(|name| name == "package.json") {
+            return Self::read_manifest_key(path);
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|error| println!("Failed to read file {}: {}", path.display(), error))
+            .ok()?;
+
+        toml::from_str(&content)
+            .map_err(|error| println!("Failed to parse file {}: {}", path.display(), error))
+            .ok()
+    }
+
+    fn read_manifest_key(path: &Path) -> Option<Self> {
+        if !path.is_file() {
+            return None;
+        }
+
+        let config = read_manifest(path)?.get(MANIFEST_CONFIG_KEY)?.clone();
+
+        serde_json::from_value(config)
+            .map_err(|error| println!("Failed to parse file {}: {}", path.display(), error))
+            .ok()
+    }
+
+    /// Returns the patterns to remove from every package, given every built-in pattern and the
+    /// selected ones
+    pub fn garbage_items(&self, built_in_items: &[&str], selected_items: &[&str]) -> Vec<String> {
+        self.disable
+            .iter()
+            .filter(|item| !built_in_items.contains(&item.as_str()))
+            .for_each(|item| println!("Unknown built-in garbage pattern: {item}"));
+
+        let selected_items = selected_items
+            .iter()
+            .filter(|_| !self.replace)
+            .filter(|item| !self.disable.iter().any(|disabled| disabled == *item))
+            .map(|item| item.to_string());
+
+        selected_items.chain(self.garbage.iter().cloned()).collect()
+    }
+
+    /// Returns the globs of the package-scoped patterns, for top-level and nested packages
+    pub fn package_globs(&self, node_modules_location: &Path) -> Vec<PathBuf> {
+        self.packages
+            .iter()
+            .flat_map(|(package, patterns)| {
+                patterns.iter().flat_map(move |pattern| {
+                    [
+                        node_modules_location.join(package).join(pattern),
+                        node_modules_location
+                            .join("**")
+                            .join("node_modules")
+                            .join(package)
+                            .join(pattern),
+                    ]
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[test]
+    fn test_from_configurations() {
+        let temp = TempDir::new().unwrap();
+        temp.child("package.json")
+            .write_str(
+                r#"{"name":"app","nmt":{"garbage":["*.flow"],"packages":{"lodash":["**/*.md"]}}}"#,
+            )
+            .unwrap();
+
+        let configurations = Cli {
+            project_root_location: temp.to_path_buf(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            ProjectConfig::from_configurations(&configurations),
+            ProjectConfig {
+                garbage: vec!["*.flow".to_owned()],
+                packages: BTreeMap::from([("lodash".to_owned(), vec!["**/*.md".to_owned()])]),
+                ..Default::default()
+            }
+        );
+
+        temp.child("nmt.toml")
+            .write_str("replace = true\ngarbage = [\"docs\"]\n\n[packages]\n\"@babel/core\" = [\"lib/**/*.map\"]\n")
+            .unwrap();
+
+        assert_eq!(
+            ProjectConfig::from_configurations(&configurations),
+            ProjectConfig {
+                garbage: vec!["docs".to_owned()],
+                replace: true,
+                packages: BTreeMap::from([(
+                    "@babel/core".to_owned(),
+                    vec!["lib/**/*.map".to_owned()]
+                )]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_garbage_items() {
+        let built_in_items = ["docs", "browser", "*.md"];
+
+        let config = ProjectConfig {
+            garbage: vec!["*.flow".to_owned()],
+            disable: vec!["browser".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            config.garbage_items(&built_in_items, &built_in_items),
+            vec!["docs", "*.md", "*.flow"]
+        );
+
+        let config = ProjectConfig {
+            garbage: vec!["*.flow".to_owned()],
+            replace: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.garbage_items(&built_in_items, &built_in_items),
+            vec!["*.flow"]
+        );
+    }
+
+    #[test]
+    fn test_package_globs() {
+        let config = ProjectConfig {
+            packages: BTreeMap::from([("lodash".to_owned(), vec!["**/*.md".to_owned()])]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.package_globs(Path::new("/app/node_modules")),
+            vec![
+                PathBuf::from("/app/node_modules/lodash/**/*.md"),
+                PathBuf::from("/app/node_modules/**/node_modules/lodash/**/*.md"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,541 @@
+//! Strip debug sections from native `.node` addons
+
+use anyhow::{anyhow, Error};
+use std::{fs, path::PathBuf};
+
+use crate::{configurations::Cli, glob::retrieve_glob_paths};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LITTLE_ENDIAN: u8 = 1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_INFO_LINK: u64 = 0x40;
+const SHT_NOBITS: u32 = 8;
+const SHT_STRTAB: u32 = 3;
+const SHT_REL: u32 = 9;
+const SHT_RELA: u32 = 4;
+const SHN_LORESERVE: u64 = 0xff00;
+
+/// Field offsets and sizes of the ELF structures, which depend on the ELF class
+struct Layout {
+    word: usize,
+    header_size: usize,
+    phoff: usize,
+    shoff: usize,
+    phentsize: usize,
+    phnum: usize,
+    shentsize: usize,
+    shnum: usize,
+    shstrndx: usize,
+    sh_flags: usize,
+    sh_offset: usize,
+    sh_size: usize,
+    sh_link: usize,
+    sh_info: usize,
+    p_offset: usize,
+    p_filesz: usize,
+}
+
+static LAYOUT_32: Layout = Layout {
+    word: 4,
+    header_size: 52,
+    phoff: 28,
+    shoff: 32,
+    phentsize: 42,
+    phnum: 44,
+    shentsize: 46,
+    shnum: 48,
+    shstrndx: 50,
+    sh_flags: 8,
+    sh_offset: 16,
+    sh_size: 20,
+    sh_link: 24,
+    sh_info: 28,
+    p_offset: 4,
+    p_filesz: 16,
+};
+
+static LAYOUT_64: Layout = Layout {
+    word: 8,
+    header_size: 64,
+    phoff: 32,
+    shoff: 40,
+    phentsize: 54,
+    phnum: 56,
+    shentsize: 58,
+    shnum: 60,
+    shstrndx: 62,
+    sh_flags: 8,
+    sh_offset: 24,
+    sh_size: 32,
+    sh_link: 40,
+    sh_info: 44,
+    p_offset: 8,
+    p_filesz: 32,
+};
+
+#[derive(Debug)]
+struct Section {
+    header_offset: usize,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u64,
+    info: u64,
+}
+
+impl Section {
+    fn is_alloc(&self) -> bool {
+        self.flags & SHF_ALLOC != 0
+    }
+
+    /// End of the section content in the file
+    fn end(&self) -> u64 {
+        if self.kind == SHT_NOBITS {
+            self.offset
+        } else {
+            self.offset.saturating_add(self.size)
+        }
+    }
+}
+
+struct Elf<'a> {
+    bytes: &'a [u8],
+    layout: &'static Layout,
+    is_little_endian: bool,
+    shoff: u64,
+    shentsize: u64,
+    shstrndx: u64,
+    sections: Vec<Section>,
+    segments: Vec<(u64, u64)>,
+}
+
+impl<'a> Elf<'a> {
+    fn read(&self, offset: usize, size: usize) -> Result<u64, Error> {
+        read_value(self.bytes, offset, size, self.is_little_endian)
+    }
+
+    /// Reads a field of a program or section header
+    fn read_field(&self, header_offset: usize, offset: usize, size: usize) -> Result<u64, Error> {
+        let offset = header_offset
+            .checked_add(offset)
+            .ok_or_else(|| anyhow!("invalid header offset"))?;
+
+        self.read(offset, size)
+    }
+
+    fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        if !bytes.starts_with(ELF_MAGIC) || bytes.len() < LAYOUT_32.header_size {
+            return Err(anyhow!("not an ELF file"));
+        }
+
+        let layout = if bytes[4] == ELF_CLASS_64 {
+            &LAYOUT_64
+        } else {
+            &LAYOUT_32
+        };
+        let mut elf = Elf {
+            bytes,
+            layout,
+            is_little_endian: bytes[5] == ELF_DATA_LITTLE_ENDIAN,
+            shoff: 0,
+            shentsize: 0,
+            shstrndx: 0,
+            sections: Vec::new(),
+            segments: Vec::new(),
+        };
+
+        let phoff = elf.read(layout.phoff, layout.word)?;
+        let phentsize = elf.read(layout.phentsize, 2)?;
+        let phnum = elf.read(layout.phnum, 2)?;
+        let shnum = elf.read(layout.shnum, 2)?;
+        elf.shoff = elf.read(layout.shoff, layout.word)?;
+        elf.shentsize = elf.read(layout.shentsize, 2)?;
+        elf.shstrndx = elf.read(layout.shstrndx, 2)?;
+
+        if elf.shoff == 0 || shnum == 0 || shnum >= SHN_LORESERVE || elf.shstrndx >= shnum {
+            return Err(anyhow!("unsupported section header table"));
+        }
+
+        for index in 0..phnum {
+            let header_offset = entry_offset(phoff, index, phentsize)?;
+            elf.segments.push((
+                elf.read_field(header_offset, layout.p_offset, layout.word)?,
+                elf.read_field(header_offset, layout.p_filesz, layout.word)?,
+            ));
+        }
+
+        for index in 0..shnum {
+            let header_offset = entry_offset(elf.shoff, index, elf.shentsize)?;
+            elf.sections.push(Section {
+                header_offset,
+                kind: u32::try_from(elf.read_field(header_offset, 4, 4)?)?,
+                flags: elf.read_field(header_offset, layout.sh_flags, layout.word)?,
+                offset: elf.read_field(header_offset, layout.sh_offset, layout.word)?,
+                size: elf.read_field(header_offset, layout.sh_size, layout.word)?,
+                link: elf.read_field(header_offset, layout.sh_link, 4)?,
+                info: elf.read_field(header_offset, layout.sh_info, 4)?,
+            });
+        }
+
+        Ok(elf)
+    }
+
+    /// Checks that the section header table, every section and every segment lie within the file
+    fn validate(&self) -> Result<(), Error> {
+        let file_size = self.bytes.len() as u64;
+        let section_headers_end =
+            entry_offset(self.shoff, self.sections.len() as u64, self.shentsize)?;
+
+        let is_valid = section_headers_end as u64 <= file_size
+            && self
+                .sections
+                .iter()
+                .all(|section| section.end() <= file_size)
+            && self
+                .segments
+                .iter()
+                .all(|(offset, size)| offset.saturating_add(*size) <= file_size)
+            && self.sections[usize::try_from(self.shstrndx)?].kind == SHT_STRTAB;
+
+        is_valid
+            .then_some(())
+            .ok_or_else(|| anyhow!("invalid ELF file"))
+    }
+
+    /// End of the content that is loaded at runtime: headers, segments and allocated sections
+    fn alloc_end(&self) -> u64 {
+        let header_end = self.layout.header_size as u64;
+        let segments_end = self
+            .segments
+            .iter()
+            .map(|(offset, size)| offset.saturating_add(*size));
+        let sections_end = self
+            .sections
+            .iter()
+            .filter(|section| section.is_alloc())
+            .map(Section::end);
+
+        segments_end.chain(sections_end).fold(header_end, u64::max)
+    }
+
+    /// End of everything the ELF structures describe, anything after it is appended data
+    fn described_end(&self) -> Result<u64, Error> {
+        let section_headers_end =
+            entry_offset(self.shoff, self.sections.len() as u64, self.shentsize)?;
+
+        Ok(self
+            .sections
+            .iter()
+            .map(Section::end)
+            .fold(self.alloc_end().max(section_headers_end as u64), u64::max))
+    }
+}
+
+/// Offset of the entry at `index` in a table of headers, failing on overflow
+fn entry_offset(table_offset: u64, index: u64, entry_size: u64) -> Result<usize, Error> {
+    let offset = index
+        .checked_mul(entry_size)
+        .and_then(|offset| offset.checked_add(table_offset))
+        .ok_or_else(|| anyhow!("invalid header table offset"))?;
+
+    Ok(usize::try_from(offset)?)
+}
+
+fn read_value(
+    bytes: &[u8],
+    offset: usize,
+    size: usize,
+    is_little_endian: bool,
+) -> Result<u64, Error> {
+    let field = offset
+        .checked_add(size)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| anyhow!("truncated ELF file"))?;
+
+    let mut value = [0u8; 8];
+    if is_little_endian {
+        value[..size].copy_from_slice(field);
+        Ok(u64::from_le_bytes(value))
+    } else {
+        value[8 - size..].copy_from_slice(field);
+        Ok(u64::from_be_bytes(value))
+    }
+}
+
+fn write_value(bytes: &mut [u8], offset: usize, size: usize, value: u64, is_little_endian: bool) {
+    let field = &mut bytes[offset..offset + size];
+
+    if is_little_endian {
+        field.copy_from_slice(&value.to_le_bytes()[..size]);
+    } else {
+        field.copy_from_slice(&value.to_be_bytes()[8 - size..]);
+    }
+}
+
+/// Drops the non-allocated sections (like `.debug_*`, `.symtab` and `.comment`) of an ELF file
+///
+/// The loaded content is copied as is, so only files whose non-allocated sections trail the
+/// allocated ones are supported. Files with data appended after the ELF content, like
+/// signatures, are left untouched.
+fn strip_elf(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let elf = Elf::parse(bytes)?;
+    elf.validate()?;
+
+    if elf.described_end()? < bytes.len() as u64 {
+        return Err(anyhow!("data appended to the ELF file"));
+    }
+
+    let alloc_end = elf.alloc_end();
+    let shstrndx = usize::try_from(elf.shstrndx)?;
+    let last_alloc_index = elf
+        .sections
+        .iter()
+        .rposition(Section::is_alloc)
+        .unwrap_or_default();
+
+    let removed_sections: Vec<&Section> = elf
+        .sections
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(index, section)| !section.is_alloc() && *index != shstrndx)
+        .map(|(_, section)| section)
+        .collect();
+
+    if removed_sections.is_empty() {
+        return Err(anyhow!("already stripped"));
+    }
+
+    let kept_sections = &elf.sections[..=last_alloc_index];
+    let has_trailing_layout = elf.sections[1..=last_alloc_index]
+        .iter()
+        .all(Section::is_alloc)
+        && shstrndx > last_alloc_index
+        && elf.shoff >= alloc_end
+        && removed_sections
+            .iter()
+            .all(|section| section.kind == SHT_NOBITS || section.offset >= alloc_end)
+        && kept_sections.iter().all(|section| {
+            let has_info_link = section.flags & SHF_INFO_LINK != 0
+                || section.kind == SHT_REL
+                || section.kind == SHT_RELA;
+
+            section.link <= last_alloc_index as u64
+                && (!has_info_link || section.info <= last_alloc_index as u64)
+        });
+
+    if !has_trailing_layout {
+        return Err(anyhow!("unsupported section layout"));
+    }
+
+    let layout = elf.layout;
+    let is_little_endian = elf.is_little_endian;
+    let shstrtab = &elf.sections[shstrndx];
+    let shstrtab_content =
+        &bytes[usize::try_from(shstrtab.offset)?..usize::try_from(shstrtab.end())?];
+
+    let mut stripped = bytes[..usize::try_from(alloc_end)?].to_vec();
+
+    let shstrtab_offset = stripped.len() as u64;
+    stripped.extend_from_slice(shstrtab_content);
+    stripped.resize(stripped.len().next_multiple_of(layout.word), 0);
+
+    let shoff = stripped.len() as u64;
+    let shentsize = usize::try_from(elf.shentsize)?;
+    for section in kept_sections.iter().chain([shstrtab]) {
+        let header = section
+            .header_offset
+            .checked_add(shentsize)
+            .and_then(|end| bytes.get(section.header_offset..end))
+            .ok_or_else(|| anyhow!("truncated section header table"))?;
+        stripped.extend_from_slice(header);
+    }
+
+    let shstrtab_header = usize::try_from(shoff)? + kept_sections.len() * shentsize;
+    write_value(
+        &mut stripped,
+        shstrtab_header + layout.sh_offset,
+        layout.word,
+        shstrtab_offset,
+        is_little_endian,
+    );
+    write_value(
+        &mut stripped,
+        layout.shoff,
+        layout.word,
+        shoff,
+        is_little_endian,
+    );
+    write_value(
+        &mut stripped,
+        layout.shnum,
+        2,
+        kept_sections.len() as u64 + 1,
+        is_little_endian,
+    );
+    write_value(
+        &mut stripped,
+        layout.shstrndx,
+        2,
+        kept_sections.len() as u64,
+        is_little_endian,
+    );
+
+    Elf::parse(&stripped)?.validate()?;
+
+    Ok(stripped)
+}
+
+/// Strips the debug sections of the `.node` addons under the `node_modules` directory
+pub fn strip(configurations: &Cli) {
+    let addons_glob = configurations
+        .node_modules_location
+        .join("**")
+        .join("*.node");
+    let addons: Vec<PathBuf> = retrieve_glob_paths(vec![addons_glob.display().to_string()])
+        .into_iter()
+        .filter(|path| path.is_file())
+        .collect();
+
+    let mut saved_bytes = 0;
+
+    for path in addons {
+        let stripped = fs::read(&path)
+            .map_err(Error::from)
+            .and_then(|bytes| Ok((bytes.len(), strip_elf(&bytes)?)));
+
+        match stripped {
+            Ok((size, stripped)) => match fs::write(&path, &stripped) {
+                Ok(()) => {
+                    let saved = size - stripped.len();
+                    saved_bytes += saved;
+                    println!("File stripped: {} ({saved} bytes saved)", path.display());
+                }
+                Err(error) => println!("Failed to write file {}: {}", path.display(), error),
+            },
+            Err(error) => println!("Skipped stripping file {}: {}", path.display(), error),
+        }
+    }
+
+    println!("Native addons stripped: {saved_bytes} bytes saved");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a little-endian 64-bit shared object with a loaded `.text` section, followed by
+    /// `.debug_info`, `.symtab` and `.shstrtab`
+    fn build_elf() -> Vec<u8> {
+        let shstrtab = b"\0.text\0.debug_info\0.symtab\0.shstrtab\0";
+        let mut bytes = vec![0u8; 0x100];
+
+        bytes[..4].copy_from_slice(ELF_MAGIC);
+        bytes[4] = ELF_CLASS_64;
+        bytes[5] = ELF_DATA_LITTLE_ENDIAN;
+        bytes[6] = 1;
+        write_value(&mut bytes, 16, 2, 3, true);
+        write_value(&mut bytes, 18, 2, 62, true);
+        write_value(&mut bytes, 20, 4, 1, true);
+        write_value(&mut bytes, LAYOUT_64.phoff, 8, 64, true);
+        write_value(&mut bytes, 52, 2, 64, true);
+        write_value(&mut bytes, LAYOUT_64.phentsize, 2, 56, true);
+        write_value(&mut bytes, LAYOUT_64.phnum, 2, 1, true);
+        write_value(&mut bytes, LAYOUT_64.shentsize, 2, 64, true);
+
+        // PT_LOAD covering the headers and `.text`
+        write_value(&mut bytes, 64, 4, 1, true);
+        write_value(&mut bytes, 64 + LAYOUT_64.p_filesz, 8, 0x110, true);
+        write_value(&mut bytes, 64 + 40, 8, 0x110, true);
+
+        bytes.extend_from_slice(&[0xc3; 0x10]);
+        let debug_info_offset = bytes.len();
+        bytes.extend_from_slice(&[0xde; 0x200]);
+        let symtab_offset = bytes.len();
+        bytes.extend_from_slice(&[0x5a; 0x48]);
+        let shstrtab_offset = bytes.len();
+        bytes.extend_from_slice(shstrtab);
+        bytes.resize(bytes.len().next_multiple_of(8), 0);
+
+        let shoff = bytes.len();
+        let sections: [(u32, u32, u64, usize, usize, u32); 5] = [
+            (0, 0, 0, 0, 0, 0),
+            (1, 1, SHF_ALLOC | 0x4, 0x100, 0x10, 0),
+            (7, 1, 0, debug_info_offset, 0x200, 0),
+            (19, 2, 0, symtab_offset, 0x48, 4),
+            (27, SHT_STRTAB, 0, shstrtab_offset, shstrtab.len(), 0),
+        ];
+        for (name, kind, flags, offset, size, link) in sections {
+            let mut header = vec![0u8; 64];
+            write_value(&mut header, 0, 4, name.into(), true);
+            write_value(&mut header, 4, 4, kind.into(), true);
+            write_value(&mut header, LAYOUT_64.sh_flags, 8, flags, true);
+            write_value(&mut header, LAYOUT_64.sh_offset, 8, offset as u64, true);
+            write_value(&mut header, LAYOUT_64.sh_size, 8, size as u64, true);
+            write_value(&mut header, LAYOUT_64.sh_link, 4, link.into(), true);
+            bytes.extend_from_slice(&header);
+        }
+
+        write_value(&mut bytes, LAYOUT_64.shoff, 8, shoff as u64, true);
+        write_value(&mut bytes, LAYOUT_64.shnum, 2, 5, true);
+        write_value(&mut bytes, LAYOUT_64.shstrndx, 2, 4, true);
+
+        bytes
+    }
+
+    #[test]
+    fn test_strip_elf() {
+        let bytes = build_elf();
+        let stripped = strip_elf(&bytes).unwrap();
+
+        assert!(stripped.len() < bytes.len());
+        assert_eq!(stripped[64..0x110], bytes[64..0x110]);
+
+        let elf = Elf::parse(&stripped).unwrap();
+        let shstrtab = &elf.sections[usize::try_from(elf.shstrndx).unwrap()];
+
+        assert_eq!(elf.sections.len(), 3);
+        assert_eq!(elf.shstrndx, 2);
+        assert_eq!(
+            &stripped[usize::try_from(shstrtab.offset).unwrap()..][..9],
+            b"\0.text\0.d"
+        );
+        assert_eq!(
+            strip_elf(&stripped).unwrap_err().to_string(),
+            "already stripped"
+        );
+    }
+
+    #[test]
+    fn test_strip_elf_untouched() {
+        assert_eq!(
+            strip_elf(b"\xcf\xfa\xed\xfe").unwrap_err().to_string(),
+            "not an ELF file"
+        );
+
+        let mut signed = build_elf();
+        signed.extend_from_slice(b"~Module signature appended~\n");
+
+        assert_eq!(
+            strip_elf(&signed).unwrap_err().to_string(),
+            "data appended to the ELF file"
+        );
+
+        let mut crafted = build_elf();
+        write_value(&mut crafted, LAYOUT_64.phoff, 8, u64::MAX, true);
+        assert!(strip_elf(&crafted).is_err());
+
+        let mut crafted = build_elf();
+        write_value(&mut crafted, LAYOUT_64.shoff, 8, u64::MAX - 8, true);
+        assert!(strip_elf(&crafted).is_err());
+
+        let mut truncated = build_elf();
+        truncated.truncate(truncated.len() - 16);
+        assert_eq!(
+            strip_elf(&truncated).unwrap_err().to_string(),
+            "invalid ELF file"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Runtime trace-related code
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::configurations::Cli;
+
+const FILE_URL_PREFIX: &str = "file://";
+
+/// Preload hook that appends every file loaded by Node.js to `NMT_TRACE_FILE`
+const TRACE_HOOK: &str = r#"// Generated by nmt: records every file loaded by Node.js.
+// Usage: NMT_TRACE_FILE=nmt-trace.log node --require ./nmt-trace.cjs dist/index.js
+'use strict';
+
+const fs = require('node:fs');
+const path = require('node:path');
+const Module = require('node:module');
+
+const traceFile = path.resolve(process.env.NMT_TRACE_FILE || 'nmt-trace.log');
+const recorded = new Set();
+
+function record(filename) {
+  if (!recorded.has(filename)) {
+    recorded.add(filename);
+    fs.appendFileSync(traceFile, `${filename}\n`);
+  }
+}
+
+const load = Module.prototype.load;
+Module.prototype.load = function (filename) {
+  record(filename);
+  return load.apply(this, arguments);
+};
+
+if (typeof Module.register === 'function') {
+  const loader = `
+    import { appendFileSync } from 'node:fs';
+    export async function load(url, context, nextLoad) {
+      if (url.startsWith('file:')) appendFileSync(${JSON.stringify(traceFile)}, url + '\\n');
+      return nextLoad(url, context);
+    }`;
+  Module.register(`data:text/javascript,${encodeURIComponent(loader)}`);
+}
+"#;
+
+/// Writes the preload hook that records the files loaded at runtime
+pub fn write_trace_hook(path: &Path) {
+    match fs::write(path, TRACE_HOOK) {
+        Ok(()) => println!("Trace hook written: {}", path.display()),
+        Err(error) => println!("Failed to write trace hook {}: {}", path.display(), error),
+    }
+}
+
+/// Converts a `file://` URL to a path, decoding percent-encoded characters
+fn file_url_to_path(url: &str) -> Option<PathBuf> {
+    let encoded_path = url.strip_prefix(FILE_URL_PREFIX)?.as_bytes();
+    let mut decoded_path = Vec::with_capacity(encoded_path.len());
+    let mut index = 0;
+
+    while index < encoded_path.len() {
+        let hex = encoded_path
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (encoded_path[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded_path.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded_path.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    Some(PathBuf::from(
+        String::from_utf8_lossy(&decoded_path).as_ref(),
+    ))
+}
+
+/// Parses a V8 coverage file, as written in the `NODE_V8_COVERAGE` directory
+fn parse_coverage(content: &str) -> Vec<PathBuf> {
+    match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(coverage) => coverage["result"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|script| script["url"].as_str())
+            .filter_map(file_url_to_path)
+            .collect(),
+        Err(error) => {
+            println!("Failed to parse coverage: {error}");
+            Vec::new()
+        }
+    }
+}
+
+/// Parses a newline-delimited load trace of paths or `file://` URLs
+///
+/// Empty lines and lines starting with `#` are ignored.
+fn parse_trace(content: &str) -> Vec<PathBuf> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            if line.starts_with(FILE_URL_PREFIX) {
+                file_url_to_path(line)
+            } else {
+                Some(PathBuf::from(line))
+            }
+        })
+        .collect()
+}
+
+fn read_trace_file(path: &Path) -> Vec<PathBuf> {
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            let is_coverage = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+            if is_coverage {
+                parse_coverage(&content)
+            } else {
+                parse_trace(&content)
+            }
+        }
+        Err(error) => {
+            println!("Failed to read trace {}: {}", path.display(), error);
+            Vec::new()
+        }
+    }
+}
+
+/// Reads a trace location: either a coverage directory or a single trace file
+fn read_trace_location(location: &Path) -> Vec<PathBuf> {
+    if !location.is_dir() {
+        return read_trace_file(location);
+    }
+
+    match fs::read_dir(location) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .flat_map(|path| read_trace_file(&path))
+            .collect(),
+        Err(error) => {
+            println!("Failed to read trace {}: {}", location.display(), error);
+            Vec::new()
+        }
+    }
+}
+
+/// Retrieves the files loaded at runtime from every trace location
+///
+/// Traced files that do not exist anymore are skipped.
+pub fn retrieve_trace_paths(configurations: &Cli) -> HashSet<PathBuf> {
+    configurations
+        .trace_location
+        .iter()
+        .flat_map(|location| {
+            read_trace_location(&configurations.project_root_location.join(location))
+        })
+        .filter_map(|path| match path.canonicalize() {
+            Ok(path) => Some(path),
+            Err(err) => {
+                println!("Error while processing {}: {}", path.display(), err);
+                None
+            }
+        })
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+
+    #[test]
+    fn test_file_url_to_path() {
+        assert_eq!(
+            file_url_to_path("file:///app/node_modules/my%20package/index.js"),
+            Some(PathBuf::from("/app/node_modules/my package/index.js"))
+        );
+        assert_eq!(
+            file_url_to_path("file:///app/100%.js"),
+            Some(PathBuf::from("/app/100%.js"))
+        );
+        assert_eq!(file_url_to_path("node:internal/modules/cjs/loader"), None);
+    }
+
+    #[test]
+    fn test_parse_trace() {
+        assert_eq!(
+            parse_trace("# comment\n/app/index.js\n\nfile:///app/esm.mjs\n"),
+            vec![
+                PathBuf::from("/app/index.js"),
+                PathBuf::from("/app/esm.mjs")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_retrieve_trace_paths() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().canonicalize().unwrap();
+
+        temp.child("index.js").touch().unwrap();
+        temp.child("node_modules/fastify/fastify.js")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/my package/index.mjs")
+            .touch()
+            .unwrap();
+        temp.child("node_modules/busboy/lib/main.js")
+            .touch()
+            .unwrap();
+        temp.child("coverage/coverage-1.json")
+            .write_str(&format!(
+                r#"{{"result":[
+                    {{"scriptId":"1","url":"node:internal/main/run_main_module","functions":[]}},
+                    {{"scriptId":"2","url":"file://{0}/index.js","functions":[]}},
+                    {{"scriptId":"3","url":"file://{0}/node_modules/my%20package/index.mjs","functions":[]}}
+                ]}}"#,
+                path.display()
+            ))
+            .unwrap();
+        temp.child("coverage/coverage-2.json")
+            .write_str(&format!(
+                r#"{{"result":[{{"scriptId":"1","url":"file://{}/node_modules/fastify/fastify.js","functions":[]}}]}}"#,
+                path.display()
+            ))
+            .unwrap();
+        temp.child("nmt-trace.log")
+            .write_str(&format!(
+                "{0}/node_modules/busboy/lib/main.js\n{0}/node_modules/busboy/lib/removed.js\n",
+                path.display()
+            ))
+            .unwrap();
+
+        let configurations = Cli {
+            project_root_location: path.clone(),
+            trace_location: vec!["coverage".into(), "nmt-trace.log".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            retrieve_trace_paths(&configurations),
+            HashSet::from([
+                path.join("index.js"),
+                path.join("node_modules/fastify/fastify.js"),
+                path.join("node_modules/my package/index.mjs"),
+                path.join("node_modules/busboy/lib/main.js"),
+            ])
+        );
+    }
+}